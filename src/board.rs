@@ -20,7 +20,10 @@ pub enum GameState {
 #[derive(PartialEq, Clone)]
 pub struct Board {
     pub turn: Player,
-    pub moves_since_capture: u8,
+    /// Plies since the last capture or pawn move, for the fifty move rule
+    pub halfmove_clock: u8,
+    /// Starts at 1 and goes up after every move of black
+    pub fullmove_number: u16,
     /// black, white
     pub can_castle_short: [bool; 2],
     /// black, white
@@ -29,7 +32,8 @@ pub struct Board {
     pub white_piece_bitboard: u64,
     pub black_piece_bitboard: u64,
     pub possible_en_passant: Option<Position>,
-    pub previous_board_states: Vec<(KeyStruct, u8)>,
+    /// Board states before every move with the move counters at that time
+    pub previous_board_states: Vec<(KeyStruct, u8, u16)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -328,8 +332,11 @@ impl Board {
             *piece_bitboard = *piece_bitboard | to;
         }
 
-        self.previous_board_states
-            .push((old_board_state, self.moves_since_capture));
+        self.previous_board_states.push((
+            old_board_state,
+            self.halfmove_clock,
+            self.fullmove_number,
+        ));
 
         if !self.is_valid() {
             self.unmake_last();
@@ -378,10 +385,13 @@ impl Board {
                 self.can_castle_long[Player::Black.idx()] = new_long_castle_rights;
             }
         }
-        if capture {
-            self.moves_since_capture = 0;
+        if capture || piece.1 == Piece::Pawn {
+            self.halfmove_clock = 0;
         } else {
-            self.moves_since_capture += 1;
+            self.halfmove_clock += 1;
+        }
+        if self.turn == Player::White {
+            self.fullmove_number += 1;
         }
         Ok(())
     }
@@ -788,6 +798,40 @@ impl Board {
         GameState::Playing
     }
 
+    /// Is the king of the side to move attacked
+    pub fn in_check(&self) -> bool {
+        let (own_bitboard, opponent) = match self.turn {
+            Player::White => (self.white_piece_bitboard, Player::Black),
+            Player::Black => (self.black_piece_bitboard, Player::White),
+        };
+        let king_pos =
+            Position::from(self.piece_bitboards[Piece::King.bitboard_index()] & own_bitboard);
+        self.attacked_by_color(&king_pos, &opponent)
+    }
+
+    /// Does the player have any pieces other than pawns and the king
+    pub fn has_non_pawn_material(&self, player: &Player) -> bool {
+        let own_bitboard = match player {
+            Player::White => self.white_piece_bitboard,
+            Player::Black => self.black_piece_bitboard,
+        };
+        let pawns_and_king = self.piece_bitboards[Piece::Pawn.bitboard_index()]
+            | self.piece_bitboards[Piece::King.bitboard_index()];
+        own_bitboard & !pawns_and_king != 0
+    }
+
+    /// Pass the turn to the opponent. Used by null move pruning in the search,
+    /// undo with [`Board::unmake_last`]
+    pub fn make_null_move(&mut self) {
+        self.previous_board_states
+            .push((self.key(), self.halfmove_clock, self.fullmove_number));
+        self.possible_en_passant = None;
+        self.turn = match self.turn {
+            Player::White => Player::Black,
+            Player::Black => Player::White,
+        };
+    }
+
     /// unmake the last move on the board
     #[inline]
     pub fn unmake_last(&mut self) {
@@ -801,7 +845,8 @@ impl Board {
                 castle_long,
                 possible_en_passant,
             },
-            moves_since_capture,
+            halfmove_clock,
+            fullmove_number,
         ) = self
             .previous_board_states
             .pop()
@@ -813,7 +858,8 @@ impl Board {
         self.can_castle_short = castle_short;
         self.can_castle_long = castle_long;
         self.possible_en_passant = possible_en_passant;
-        self.moves_since_capture = moves_since_capture;
+        self.halfmove_clock = halfmove_clock;
+        self.fullmove_number = fullmove_number;
     }
 
     pub fn number_of_attacks_by_color(&self, pos: &Position, color: &Player) -> isize {
@@ -1029,7 +1075,7 @@ impl Board {
 
     #[inline]
     fn is_fifty_move_rule(&self) -> bool {
-        if self.moves_since_capture >= 100 {
+        if self.halfmove_clock >= 100 {
             return true;
         }
         return false;
@@ -1051,7 +1097,7 @@ impl Board {
     fn is_threefold_rep(&self) -> bool {
         let mut counts = FxHashMap::default();
         let mut piece_count = (self.white_piece_bitboard | self.black_piece_bitboard).count_ones();
-        for (x, _) in self.previous_board_states.iter().rev() {
+        for (x, _, _) in self.previous_board_states.iter().rev() {
             if (x.white_piece_bitboard | x.black_piece_bitboard).count_ones() != piece_count {
                 piece_count = (x.white_piece_bitboard | x.black_piece_bitboard).count_ones();
                 return false;
@@ -1117,12 +1163,177 @@ impl Board {
     }
 }
 
+impl Board {
+    /// Parse a position in Forsyth-Edwards Notation. The move counters are
+    /// optional.
+    pub fn from_fen(fen: &str) -> Result<Board, &'static str> {
+        let mut fields = fen.split_whitespace();
+        let mut board = Board {
+            turn: Player::White,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            can_castle_short: [false, false],
+            can_castle_long: [false, false],
+            piece_bitboards: [0; 6],
+            white_piece_bitboard: 0,
+            black_piece_bitboard: 0,
+            possible_en_passant: None,
+            previous_board_states: Vec::with_capacity(300),
+        };
+
+        let placement = fields.next().ok_or("empty fen")?;
+        let ranks = placement.split('/').collect::<Vec<_>>();
+        if ranks.len() != 8 {
+            return Err("fen needs 8 ranks");
+        }
+        for (i, rank) in ranks.into_iter().enumerate() {
+            let row = 7 - i as i64;
+            let mut col = 0;
+            for c in rank.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    col += empty as i64;
+                    continue;
+                }
+                let piece = match c.to_ascii_lowercase() {
+                    'p' => Piece::Pawn,
+                    'n' => Piece::Knight,
+                    'b' => Piece::Bishop,
+                    'r' => Piece::Rook,
+                    'q' => Piece::Queen,
+                    'k' => Piece::King,
+                    _ => return Err("invalid piece in fen"),
+                };
+                let pos = Position::new(row, col);
+                if !pos.valid() {
+                    return Err("fen rank too long");
+                }
+                board.piece_bitboards[piece.bitboard_index()] |= pos.bitboard();
+                if c.is_ascii_uppercase() {
+                    board.white_piece_bitboard |= pos.bitboard();
+                } else {
+                    board.black_piece_bitboard |= pos.bitboard();
+                }
+                col += 1;
+            }
+            if col != 8 {
+                return Err("fen rank has wrong length");
+            }
+        }
+
+        for color in [board.white_piece_bitboard, board.black_piece_bitboard] {
+            if (board.piece_bitboards[Piece::King.bitboard_index()] & color).count_ones() != 1 {
+                return Err("fen needs exactly one king per side");
+            }
+        }
+
+        board.turn = match fields.next() {
+            Some("w") => Player::White,
+            Some("b") => Player::Black,
+            _ => return Err("invalid side to move in fen"),
+        };
+
+        for c in fields.next().ok_or("no castling rights in fen")?.chars() {
+            match c {
+                'K' => board.can_castle_short[Player::White.idx()] = true,
+                'Q' => board.can_castle_long[Player::White.idx()] = true,
+                'k' => board.can_castle_short[Player::Black.idx()] = true,
+                'q' => board.can_castle_long[Player::Black.idx()] = true,
+                '-' => (),
+                _ => return Err("invalid castling rights in fen"),
+            }
+        }
+
+        board.possible_en_passant = match fields.next().ok_or("no en passant square in fen")? {
+            "-" => None,
+            square => {
+                let mut chars = square.chars();
+                let col = match chars.next() {
+                    Some(c @ 'a'..='h') => c as i64 - 'a' as i64,
+                    _ => return Err("invalid en passant square in fen"),
+                };
+                let row = match chars.next() {
+                    Some(c @ '1'..='8') => c as i64 - '1' as i64,
+                    _ => return Err("invalid en passant square in fen"),
+                };
+                Some(Position::new(row, col))
+            }
+        };
+
+        if let Some(halfmoves) = fields.next() {
+            board.halfmove_clock = halfmoves.parse().map_err(|_| "invalid halfmove clock")?;
+        }
+        if let Some(fullmoves) = fields.next() {
+            board.fullmove_number = match fullmoves.parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err("invalid fullmove number"),
+            };
+        }
+
+        Ok(board)
+    }
+
+    /// The position in Forsyth-Edwards Notation
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for row in (0..8).rev() {
+            let mut empty = 0;
+            for col in 0..8 {
+                match self.piece_type(&Position::new(row, col)) {
+                    Some((player, piece)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let c = ['p', 'n', 'b', 'r', 'q', 'k'][piece.bitboard_index()];
+                        fen.push(match player {
+                            Player::White => c.to_ascii_uppercase(),
+                            Player::Black => c,
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if row > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(match self.turn {
+            Player::White => " w ",
+            Player::Black => " b ",
+        });
+
+        let castling = [
+            (self.can_castle_short[Player::White.idx()], 'K'),
+            (self.can_castle_long[Player::White.idx()], 'Q'),
+            (self.can_castle_short[Player::Black.idx()], 'k'),
+            (self.can_castle_long[Player::Black.idx()], 'q'),
+        ]
+        .into_iter()
+        .filter_map(|(allowed, c)| allowed.then_some(c))
+        .collect::<String>();
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
+
+        match &self.possible_en_passant {
+            Some(p) => fen.push_str(&format!(" {}{} ", (b'a' + p.col as u8) as char, p.row + 1)),
+            None => fen.push_str(" - "),
+        }
+
+        fen.push_str(&format!("{} {}", self.halfmove_clock, self.fullmove_number));
+        fen
+    }
+}
+
 impl Default for Board {
     /// Return the initial position
     fn default() -> Self {
         Self {
             turn: Player::White,
-            moves_since_capture: 0,
+            halfmove_clock: 0,
+            fullmove_number: 1,
             can_castle_long: [true, true],
             can_castle_short: [true, true],
             piece_bitboards: [
@@ -1195,3 +1406,50 @@ impl Display for Board {
         write!(f, "{string}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make(board: &mut Board, cmove: &str) {
+        board.make_move(&cmove.parse().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn move_counters() {
+        let mut board = Board::default();
+        make(&mut board, "g1f3");
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (1, 1));
+        make(&mut board, "g8f6");
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (2, 2));
+        make(&mut board, "e2e4");
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (0, 2));
+        make(&mut board, "f6e4");
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (0, 3));
+        make(&mut board, "b1c3");
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (1, 3));
+
+        board.unmake_last();
+        board.unmake_last();
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (0, 2));
+    }
+
+    #[test]
+    fn fen_move_counters() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (2, 3));
+        assert_eq!(board.to_fen(), fen);
+        make(&mut board, "f1b5");
+        make(&mut board, "a7a6");
+        assert!(board.to_fen().ends_with(" 0 4"));
+        assert!(Board::from_fen("8/8/8/8/8/8/8/K6k w - - 0 0").is_err());
+    }
+
+    #[test]
+    fn fen_needs_both_kings() {
+        assert!(Board::from_fen("8/8/8/8/8/8/8/K6k w - - 0 1").is_ok());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/K7 w - - 0 1").is_err());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/KK5k w - - 0 1").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Reader for opening books in the Polyglot `.bin` format
+
+use std::{fs, io, path::Path};
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    board::{Board, Position},
+    cmove::Move,
+    piece::Piece,
+    zobrist::polyglot_key,
+};
+
+/// Size of one entry: key (8), move (2), weight (2), learn (4), all big endian
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+}
+
+/// Entries of a Polyglot book, sorted by key
+#[derive(Debug, Clone, Default)]
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+}
+
+impl OpeningBook {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from_bytes(&fs::read(path)?))
+    }
+
+    /// Incomplete trailing entries are ignored
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|e| BookEntry {
+                key: u64::from_be_bytes(e[0..8].try_into().unwrap()),
+                raw_move: u16::from_be_bytes([e[8], e[9]]),
+                weight: u16::from_be_bytes([e[10], e[11]]),
+            })
+            .collect::<Vec<_>>();
+        // Books are sorted already, but lookups rely on it
+        entries.sort_by_key(|e| e.key);
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Legal book moves for the position with their weights
+    pub fn moves(&self, board: &mut Board) -> Vec<(Move, u16)> {
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|e| e.key < key);
+        let legal = board.get_valid_moves();
+        self.entries[start..]
+            .iter()
+            .take_while(|e| e.key == key)
+            .map(|e| (decode_move(board, e.raw_move), e.weight))
+            .filter(|(m, _)| legal.contains(m))
+            .collect()
+    }
+
+    /// A book move chosen at random, proportionally to the book weights
+    pub fn pick(&self, board: &mut Board, rng: &mut StdRng) -> Option<Move> {
+        let moves = self.moves(board);
+        let total = moves.iter().map(|(_, w)| *w as u32).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+        let mut choice = rng.gen_range(0..total);
+        for (m, weight) in moves {
+            if choice < weight as u32 {
+                return Some(m);
+            }
+            choice -= weight as u32;
+        }
+        None
+    }
+}
+
+/// Polyglot moves are `to file, to row, from file, from row, promotion` in
+/// 3 bits each. Castling is written as the king capturing its own rook.
+fn decode_move(board: &Board, raw: u16) -> Move {
+    let square = |bits: u16| Position::new((bits >> 3 & 7) as i64, (bits & 7) as i64);
+    let from = square(raw >> 6);
+    let mut to = square(raw);
+
+    let is_king = board
+        .piece_type(&from)
+        .is_some_and(|(_, piece)| piece == Piece::King);
+    if is_king && from.col == 4 && from.row == to.row {
+        match to.col {
+            7 => to.col = 6,
+            0 => to.col = 2,
+            _ => (),
+        }
+    }
+
+    match (raw >> 12) & 7 {
+        0 => Move::new(&from, &to),
+        1 => Move::promotion(&from, &to, Piece::Knight),
+        2 => Move::promotion(&from, &to, Piece::Bishop),
+        3 => Move::promotion(&from, &to, Piece::Rook),
+        _ => Move::promotion(&from, &to, Piece::Queen),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polyglot encodes castling as the king capturing its own rook
+    #[test]
+    fn decode_castling() {
+        let board = Board::default();
+        let raw = |from: u16, to: u16| from << 6 | to;
+        assert_eq!(decode_move(&board, raw(4, 7)), "e1g1".parse().unwrap());
+        assert_eq!(decode_move(&board, raw(4, 0)), "e1c1".parse().unwrap());
+        assert_eq!(decode_move(&board, raw(60, 63)), "e8g8".parse().unwrap());
+        assert_eq!(decode_move(&board, raw(60, 56)), "e8c8".parse().unwrap());
+        // Only the king's moves are changed
+        assert_eq!(decode_move(&board, raw(12, 28)), "e2e4".parse().unwrap());
+    }
+}
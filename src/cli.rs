@@ -0,0 +1,88 @@
+//! Flag parsing shared by the binaries
+
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{game::Adjudication, parse, tree_evaluator::Bot, User};
+
+/// Remove `flag` and the value following it from the arguments and return the value
+pub fn take_flag(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    let Some(i) = args.iter().position(|a| a == flag) else {
+        return Ok(None);
+    };
+    args.remove(i);
+    if i < args.len() {
+        Ok(Some(args.remove(i)))
+    } else {
+        Err(format!("{flag} needs a value"))
+    }
+}
+
+/// Like `take_flag`, but parse the value
+pub fn take_parsed<T>(args: &mut Vec<String>, flag: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    take_flag(args, flag)?
+        .map(|v| {
+            v.parse::<T>()
+                .map_err(|e| format!("invalid {flag} {v}: {e}"))
+        })
+        .transpose()
+}
+
+/// Remove a flag without a value, returns whether it was given
+pub fn take_switch(args: &mut Vec<String>, flag: &str) -> bool {
+    let given = args.iter().any(|a| a == flag);
+    args.retain(|a| a != flag);
+    given
+}
+
+/// Fail on arguments that no flag took
+pub fn finish(args: &[String]) -> Result<(), String> {
+    match args.first() {
+        Some(a) => Err(format!("unknown argument {a}, see --help")),
+        None => Ok(()),
+    }
+}
+
+/// A bot written the same way as on the command line of the other binaries,
+/// e.g. `DEFAULT` or `SEARCH all THREADS 2 DEFAULT`
+pub fn bot(spec: &str) -> Result<Bot, Box<dyn Error>> {
+    match parse::parse(&mut spec.split_whitespace().map(String::from))? {
+        User::Bot(b) => Ok(*b),
+        User::Human => Err(format!("{spec:?} is not a bot"))?,
+    }
+}
+
+/// The random number generator for `--seed`. Without a seed a random one is
+/// picked and printed so that the run can be replayed.
+pub fn seeded_rng(args: &mut Vec<String>) -> Result<StdRng, String> {
+    let seed = match take_parsed::<u64>(args, "--seed")? {
+        Some(seed) => seed,
+        None => {
+            let seed = rand::thread_rng().gen();
+            println!("seed: {seed}");
+            seed
+        }
+    };
+    Ok(StdRng::seed_from_u64(seed))
+}
+
+/// Number of games to play at once from `--concurrency`, one at a time by default
+pub fn concurrency(args: &mut Vec<String>) -> Result<usize, String> {
+    Ok(take_parsed::<usize>(args, "--concurrency")?
+        .unwrap_or(1)
+        .max(1))
+}
+
+/// Adjudication of bot games from `--resign`, `--draw` and `--max-moves`
+pub fn adjudication(args: &mut Vec<String>) -> Result<Adjudication, String> {
+    Ok(Adjudication {
+        resign: take_parsed(args, "--resign")?,
+        draw: take_parsed(args, "--draw")?,
+        max_moves: take_parsed(args, "--max-moves")?,
+    })
+}
@@ -1,8 +1,8 @@
-use std::str::FromStr;
+use std::{fmt::Display, str::FromStr};
 
 use crate::{board::Position, piece::Piece};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Move {
     bit_rep: u16,
 }
@@ -11,7 +11,6 @@ impl FromStr for Move {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut parts = s.trim().chars().peekable();
-        print!("{s}");
         if parts.peek().is_none() {
             return Err("empty move string");
         }
@@ -64,10 +63,10 @@ impl FromStr for Move {
         };
 
         let promotion = match parts.next() {
-            Some('R') => Piece::Rook.bitboard_index(),
-            Some('B') => Piece::Bishop.bitboard_index(),
-            Some('N') => Piece::Knight.bitboard_index(),
-            Some('Q') => Piece::Queen.bitboard_index(),
+            Some('R' | 'r') => Piece::Rook.bitboard_index(),
+            Some('B' | 'b') => Piece::Bishop.bitboard_index(),
+            Some('N' | 'n') => Piece::Knight.bitboard_index(),
+            Some('Q' | 'q') => Piece::Queen.bitboard_index(),
             Some(_) => return Err("Invalid promotion piece"),
             None => 0,
         } as u16;
@@ -78,6 +77,21 @@ impl FromStr for Move {
     }
 }
 
+/// Long algebraic notation as used by UCI, e.g. `e2e4` or `e7e8q`
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let square = |p: Position| format!("{}{}", (b'a' + p.col as u8) as char, p.row + 1);
+        write!(f, "{}{}", square(self.from()), square(self.to()))?;
+        match self.promotion_bitboard_index() {
+            1 => write!(f, "n"),
+            2 => write!(f, "b"),
+            3 => write!(f, "r"),
+            4 => write!(f, "q"),
+            _ => Ok(()),
+        }
+    }
+}
+
 impl Move {
     pub fn from(&self) -> Position {
         // extract starting square from bits
@@ -113,6 +127,15 @@ impl Move {
         (self.bit_rep >> 12) as usize
     }
 
+    /// The packed 16 bit representation of the move
+    pub fn bits(&self) -> u16 {
+        self.bit_rep
+    }
+
+    pub fn from_bits(bit_rep: u16) -> Move {
+        Move { bit_rep }
+    }
+
     pub fn without_promotion(&self) -> Move {
         Move {
             bit_rep: self.bit_rep & 0b111111111111,
@@ -1,231 +1,220 @@
-use chlang::board::{self, Player};
-use chlang::cmove::Move;
-use chlang::game;
-use chlang::parse;
+use chlang::board::{self, GameState, Player};
+use chlang::cli;
+use chlang::game::{self, Adjudicated, Adjudication, TimeControl};
+use chlang::openings::{self, Opening};
+use chlang::pool;
+use chlang::stats::{MatchScore, Sprt, SprtState};
+use chlang::tree_evaluator::Bot;
 use chlang::User;
-use std::io::BufRead;
-
-pub fn run(b1: &mut User, b2: &mut User) -> [usize; 3] {
-    match b1 {
-        User::Human => panic!("can't bench with human player"),
-        _ => (),
-    }
-    match b2 {
-        User::Human => panic!("can't bench with human player"),
-        _ => (),
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashMap, fs::write};
+
+/// Score of bot 1 over all openings
+fn score(results: &[OpeningResult]) -> MatchScore {
+    let mut total = MatchScore::default();
+    for result in results {
+        total.wins += result.wins[0];
+        total.losses += result.wins[1];
+        total.draws += result.wins[2];
     }
+    total
+}
 
-    let mut stdin = std::io::BufReader::new(std::io::stdin());
-
-    // get iterations
-    println!("Iterations ? ");
-    let mut iters = String::new();
-    stdin.read_line(&mut iters).unwrap();
-    iters = iters.trim().to_string();
-    let iters = iters.parse::<usize>().unwrap();
-
-    let mut wins = [0, 0, 0];
+/// Bot 1 wins, bot 2 wins and draws from one start position
+pub struct OpeningResult {
+    pub name: String,
+    pub wins: [usize; 3],
+}
 
-    for i in 0..(iters * 2) {
-        if i % 2 == 0 {
-            match game::run(b1, b2) {
-                board::GameState::Win(board::Player::White) => wins[0] += 1,
-                board::GameState::Win(board::Player::Black) => wins[1] += 1,
-                board::GameState::Draw => wins[2] += 1,
-                _ => (),
+/// Play `games` games, going through the openings in order and playing each
+/// one with both colors. `concurrency` games are played at once. Also returns
+/// how many games were adjudicated for every reason.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    b1: &Bot,
+    b2: &Bot,
+    openings: &[Opening],
+    games: usize,
+    adjudication: &Adjudication,
+    time_control: Option<&TimeControl>,
+    sprt: Option<&Sprt>,
+    concurrency: usize,
+    rng: &mut StdRng,
+) -> (Vec<OpeningResult>, HashMap<Adjudicated, usize>) {
+    let mut results = openings
+        .iter()
+        .map(|o| OpeningResult {
+            name: o.name.clone(),
+            wins: [0, 0, 0],
+        })
+        .collect::<Vec<_>>();
+    let mut adjudicated = HashMap::new();
+
+    // The seeds are drawn up front so the games don't depend on the order they finish in
+    let games = (0..games)
+        .map(|i| ((i / 2) % openings.len(), i % 2 == 1, rng.gen::<u64>()))
+        .collect::<Vec<_>>();
+
+    pool::run(
+        games,
+        concurrency,
+        |(opening, swapped, seed)| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let board = openings[opening].board.clone();
+            let (mut p1, mut p2) = (
+                User::Bot(Box::new(b1.bot_clone())),
+                User::Bot(Box::new(b2.bot_clone())),
+            );
+            let (white, black) = if swapped {
+                (&mut p2, &mut p1)
+            } else {
+                (&mut p1, &mut p2)
+            };
+            let outcome = game::run_with(
+                board,
+                white,
+                black,
+                adjudication,
+                time_control,
+                &mut rng,
+                |_, _| (),
+            );
+            (opening, swapped, outcome)
+        },
+        |(opening, swapped, outcome)| {
+            if let Some(reason) = outcome.adjudicated {
+                *adjudicated.entry(reason).or_insert(0) += 1;
             }
-        } else {
-            match game::run(b2, b1) {
-                board::GameState::Win(board::Player::White) => wins[1] += 1,
-                board::GameState::Win(board::Player::Black) => wins[0] += 1,
-                board::GameState::Draw => wins[2] += 1,
-                _ => (),
+            let wins = &mut results[opening].wins;
+            match (outcome.state, swapped) {
+                (GameState::Win(Player::White), false) | (GameState::Win(Player::Black), true) => {
+                    wins[0] += 1
+                }
+                (GameState::Win(Player::Black), false) | (GameState::Win(Player::White), true) => {
+                    wins[1] += 1
+                }
+                (GameState::Draw, _) => wins[2] += 1,
+                (GameState::Playing, _) => unreachable!("games are played until they end"),
             }
-        }
-    }
 
-    wins
-}
+            // Stop as soon as the test is decided
+            sprt.is_none_or(|sprt| sprt.state(&score(&results)) == SprtState::Continue)
+        },
+    );
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /*
-    Only for development, shows a stacktrace on stack overflows
-    (added since I accidentaly ifinitely called a recursive function.
-    Solved by adding a boolean flag to the get_pseudo_legal_king_moves method)
-    */
-    //unsafe { backtrace_on_stack_overflow::enable() };
+    (results, adjudicated)
+}
 
-    let mut a = std::env::args();
+const HELP: &str = "Play two bots against each other and report the results
+
+Usage: compare --bot1 <BOT> --bot2 <BOT> [OPTIONS]
+
+Options:
+  --bot1 <BOT>          First bot, e.g. DEFAULT or \"SEARCH all THREADS 2 DEFAULT\"
+  --bot2 <BOT>          Second bot
+  --games <N>           Number of games [default: 2 per opening]
+  --depth <N>           Search depth of both bots, overriding the bot's own
+  --tc <[MOVES/]BASE[+INC]>
+                        Play with clocks instead of a fixed depth, times in seconds,
+                        e.g. 10+0.1 or 40/60. A bot over its time loses.
+  --out <FILE>          Write the results to a file instead of stdout
+  --openings <FILE>     EPD/FEN file with start positions, each played with both colors
+  --resign <SCORE,MOVES>
+                        Adjudicate a win when both bots' scores are beyond SCORE for
+                        MOVES moves in a row
+  --draw <SCORE,MOVES,AFTER>
+                        Adjudicate a draw when both bots' scores are within SCORE of 0
+                        for MOVES moves in a row after move AFTER
+  --max-moves <N>       Adjudicate a draw after N moves
+  --sprt <E0,E1,A,B>    Stop early when a SPRT of elo0 against elo1 is decided
+  --concurrency <N>     Number of games played at once [default: 1]
+  --seed <N>            Seed for reproducible matches
+  --help                Print this help";
 
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     // skip name of program
-    a.next();
-
-    let mut white_player = parse::parse(&mut a)?;
-
-    let mut black_player = parse::parse(&mut a)?;
-
-    #[cfg(feature = "gui")]
-    {
-        let mut app = game::Game {
-            white_player,
-            black_player,
-            ..Default::default()
-        };
-
-        return Ok(game::run(&mut app));
-    }
-
-    #[cfg(feature = "compare")]
-    {
-        let mut stdin = std::io::BufReader::new(std::io::stdin());
-
-        // get output file
-        println!("Ouput ? ");
-        let mut out_file_path = String::new();
-        stdin.read_line(&mut out_file_path).unwrap();
-        out_file_path = out_file_path.trim().to_string();
-        let out_file_path = PathBuf::from(out_file_path);
-
-        // Benchmark
-        let result = bench::run(&mut white_player, &mut black_player);
-
-        write(
-            out_file_path,
-            &format!(
-                "Bot 1 wins: {}\nBot 2 wins: {}\nDraws: {}",
-                result[0], result[1], result[2]
-            ),
-        )?;
-
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if cli::take_switch(&mut args, "--help") {
+        println!("{HELP}");
         return Ok(());
     }
 
-    #[cfg(feature = "train")]
-    {
-        let mut stdin = std::io::BufReader::new(std::io::stdin());
-
-        println!("Load checkpoint? (type no to start from default)");
-        let mut checkpoint_path = String::new();
-        stdin.read_line(&mut checkpoint_path).unwrap();
-        checkpoint_path = checkpoint_path.trim().to_string();
-
-        if &checkpoint_path != "no" {
-            let mut checkpoint_file = File::open(&checkpoint_path)?;
-            let mut checkpoint_content = String::new();
-            checkpoint_file.read_to_string(&mut checkpoint_content);
-            checkpoint_content = checkpoint_content.trim().to_string();
-
-            let mut checkpoint_iter = checkpoint_content.split(" ").map(|x| x.to_owned());
-
-            white_player = parse(&mut checkpoint_iter)?;
-            black_player = parse(&mut checkpoint_iter)?;
-        } else {
-            white_player = User::Bot(Bot {
-                evaluator: Box::new(evaluator_0::Evaluator::default()),
-                search_depth: 4,
-                cache: FxHashMap::default(),
-            });
-            black_player = User::Bot(Bot {
-                evaluator: Box::new(evaluator_0::Evaluator::default()),
-                search_depth: 4,
-                cache: FxHashMap::default(),
-            });
-        }
-
-        println!("Save to checkpoint?");
-        let mut checkpoint_path = String::new();
-        stdin.read_line(&mut checkpoint_path).unwrap();
-        checkpoint_path = checkpoint_path.trim().to_string();
-
-        let (mut b1, mut b2) = match (white_player, black_player) {
-            (User::Bot(b1), User::Bot(b2)) => (b1, b2),
-            _ => Err("Can't train with human bots")?,
+    let mut b1 = cli::bot(&cli::take_flag(&mut args, "--bot1")?.ok_or("--bot1 is required")?)?;
+    let mut b2 = cli::bot(&cli::take_flag(&mut args, "--bot2")?.ok_or("--bot2 is required")?)?;
+    if let Some(depth) = cli::take_parsed::<u8>(&mut args, "--depth")? {
+        b1.search_depth = depth;
+        b2.search_depth = depth;
+    }
+    let openings = match cli::take_flag(&mut args, "--openings")? {
+        Some(path) => openings::read(path)?,
+        None => vec![Opening {
+            name: String::from("startpos"),
+            board: board::Board::default(),
+        }],
+    };
+    if openings.is_empty() {
+        Err("no positions in the openings file")?;
+    }
+    let games = cli::take_parsed::<usize>(&mut args, "--games")?.unwrap_or(2 * openings.len());
+    let out = cli::take_flag(&mut args, "--out")?;
+    let adjudication = cli::adjudication(&mut args)?;
+    let time_control = cli::take_parsed::<TimeControl>(&mut args, "--tc")?;
+    let sprt = cli::take_parsed::<Sprt>(&mut args, "--sprt")?;
+    let concurrency = cli::concurrency(&mut args)?;
+    let mut rng = cli::seeded_rng(&mut args)?;
+    cli::finish(&args)?;
+
+    // Benchmark
+    let (results, adjudicated) = run(
+        &b1,
+        &b2,
+        &openings,
+        games,
+        &adjudication,
+        time_control.as_ref(),
+        sprt.as_ref(),
+        concurrency,
+        &mut rng,
+    );
+    let total = score(&results);
+
+    let mut output = format!(
+        "Bot 1 wins: {}\nBot 2 wins: {}\nDraws: {}\n{total}",
+        total.wins, total.losses, total.draws
+    );
+    if adjudication != Adjudication::default() || time_control.is_some() {
+        let reasons = Adjudicated::ALL
+            .iter()
+            .map(|r| format!("{} {r}", adjudicated.get(r).unwrap_or(&0)))
+            .collect::<Vec<_>>();
+        output.push_str(&format!("\nAdjudicated: {}", reasons.join(", ")));
+    }
+    if let Some(sprt) = sprt {
+        let state = match sprt.state(&total) {
+            SprtState::Continue => "inconclusive",
+            SprtState::AcceptH0 => "H0 accepted",
+            SprtState::AcceptH1 => "H1 accepted",
         };
-
-        train::train(b1, b2, checkpoint_path);
-
-        return Ok(());
+        output.push_str(&format!(
+            "\n{sprt}\nLLR: {:.2} ({:.2}, {:.2}) {state}",
+            sprt.llr(&total),
+            sprt.lower_bound(),
+            sprt.upper_bound()
+        ));
     }
-
-    let mut board = board::Board::default();
-
-    let mut stdin = std::io::BufReader::new(std::io::stdin());
-    println!("\x1b[2J\x1b[H");
-    println!("{}", board);
-
-    loop {
-        match board.turn {
-            Player::White => match white_player {
-                User::Human => {
-                    let mut input = String::new();
-                    stdin.read_line(&mut input);
-                    if input.trim() == "u" {
-                        board.unmake_last();
-                    } else {
-                        let cmove: Result<Move, &str> = input.parse();
-
-                        if cmove.is_ok() {
-                            board.make_move(&cmove.clone().unwrap());
-                        }
-                    }
-                }
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut board);
-                    if let Some(m) = cmove {
-                        board.make_move(&m);
-                    }
-                }
-            },
-            Player::Black => match black_player {
-                User::Human => {
-                    let mut input = String::new();
-                    stdin.read_line(&mut input);
-                    if input.trim() == "u" {
-                        board.unmake_last();
-                    } else {
-                        let cmove: Result<Move, &str> = input.parse();
-
-                        if cmove.is_ok() {
-                            board.make_move(&cmove.clone().unwrap());
-                        }
-                    }
-                }
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut board);
-                    if let Some(m) = cmove {
-                        board.make_move(&m);
-                    }
-                }
-            },
-        }
-
-        println!("\x1b[2J\x1b[H");
-        println!("{}", board);
-
-        match board.get_game_state() {
-            board::GameState::Draw => {
-                #[cfg(not(feature = "compare"))]
-                println!("DRAW");
-                #[cfg(feature = "compare")]
-                println!("0");
-                break;
-            }
-            board::GameState::Win(board::Player::White) => {
-                #[cfg(not(feature = "compare"))]
-                println!("White Wins");
-                #[cfg(feature = "compare")]
-                println!("1");
-                break;
-            }
-            board::GameState::Win(board::Player::Black) => {
-                #[cfg(not(feature = "compare"))]
-                println!("Black Wins");
-                #[cfg(feature = "compare")]
-                println!("-1");
-                break;
-            }
-            _ => (),
+    if openings.len() > 1 {
+        output.push_str("\n\nPer opening (bot 1 wins / bot 2 wins / draws):\n");
+        for result in results.iter() {
+            output.push_str(&format!(
+                "{}: {} / {} / {}\n",
+                result.name, result.wins[0], result.wins[1], result.wins[2]
+            ));
         }
     }
+    match out {
+        Some(path) => write(path, output)?,
+        None => println!("{output}"),
+    }
+
     Ok(())
 }
@@ -1,7 +1,11 @@
+use crate::evaluators::evaluator_0;
+
 struct State {
     state_type: StateType,
     stage_piece: Stage,
     extra_stage: ExtraStage,
+    pawn_stage: PawnStage,
+    king_stage: KingStage,
 }
 
 enum StateType {
@@ -12,6 +16,8 @@ enum StateType {
     Rook,
     Queen,
     King,
+    PawnStructure,
+    KingSafety,
 }
 
 enum ExtraStage {
@@ -26,6 +32,58 @@ enum Stage {
     Moves,
 }
 
+enum PawnStage {
+    Doubled,
+    Isolated,
+    Backward,
+    Connected,
+    Chained,
+    Passed,
+}
+
+enum KingStage {
+    Shield,
+    OpenFiles,
+    Attackers,
+    Distance,
+}
+
+/// Values of `evaluator_1`, only given in programs with a `PawnStructure:` section
+#[derive(Default)]
+struct PawnStructureValues {
+    doubled: u8,
+    isolated: u8,
+    backward: u8,
+    connected: u8,
+    chained: u8,
+    /// Ranks 2 to 7
+    passed: Vec<u8>,
+}
+
+/// Values of `evaluator_2`, only given in programs with a `KingSafety:` section
+#[derive(Default)]
+struct KingSafetyValues {
+    /// One and two rows in front of the king
+    shield: Vec<u8>,
+    /// Semi-open and open
+    open_files: Vec<u8>,
+    /// Knights, bishops, rooks and queens
+    attackers: Vec<u8>,
+    /// Knights, bishops, rooks and queens
+    distance: Vec<u8>,
+}
+
+impl KingSafetyValues {
+    fn push(&mut self, stage: &KingStage, val: u8) {
+        match stage {
+            KingStage::Shield => self.shield.push(val),
+            KingStage::OpenFiles => self.open_files.push(val),
+            KingStage::Attackers => self.attackers.push(val),
+            KingStage::Distance => self.distance.push(val),
+        }
+    }
+}
+
 struct PieceValues {
     base: u8,
     position: Vec<u8>,
@@ -42,14 +100,66 @@ struct Parsed {
     rook: PieceValues,
     queen: PieceValues,
     king: PieceValues,
+    pawn_structure: Option<PawnStructureValues>,
+    king_safety: Option<KingSafetyValues>,
+}
+
+/// Split a program into its `Midgame:` and `Endgame:` sections. None if it
+/// has neither, then it is a single `evaluator_0` program.
+fn phases(source: &str) -> Option<(String, String)> {
+    let is_header = |line: &str| matches!(line.to_lowercase().as_str(), "midgame:" | "endgame:");
+    if !source.lines().any(|l| is_header(l.trim())) {
+        return None;
+    }
+    let mut sections: [Option<String>; 2] = [None, None];
+    let mut current: Option<usize> = None;
+    for line in source.lines() {
+        let trimmed = line.trim();
+        let i = match trimmed.to_lowercase().as_str() {
+            "midgame:" => 0,
+            "endgame:" => 1,
+            // Empty lines can separate the sections
+            "" => continue,
+            _ => {
+                let Some(i) = current else {
+                    panic!("{trimmed:?} is outside of the Midgame: and Endgame: sections");
+                };
+                let section = sections[i].as_mut().unwrap();
+                section.push_str(trimmed);
+                section.push('\n');
+                continue;
+            }
+        };
+        if sections[i].is_some() {
+            panic!("more than one {trimmed} section");
+        }
+        sections[i] = Some(String::new());
+        current = Some(i);
+    }
+    match sections {
+        [Some(midgame), Some(endgame)] => Some((midgame, endgame)),
+        _ => panic!("a tapered program needs both a Midgame: and an Endgame: section"),
+    }
 }
 
+/// Compile a program to the string representation of its evaluator. Programs
+/// with `Midgame:` and `Endgame:` sections, each a whole `evaluator_0`
+/// program, compile to a tapered evaluator.
 pub fn compile(source: String) -> String {
+    if let Some((midgame, endgame)) = phases(&source) {
+        let (midgame, endgame) = (compile(midgame), compile(endgame));
+        if midgame.len() != evaluator_0::BYTES || endgame.len() != evaluator_0::BYTES {
+            panic!("PawnStructure: and KingSafety: can't be used in a tapered program");
+        }
+        return midgame + &endgame;
+    }
     let mut parsed = Parsed {
         state: State {
             state_type: StateType::Extra,
             stage_piece: Stage::Base,
             extra_stage: ExtraStage::ShortCastle,
+            pawn_stage: PawnStage::Doubled,
+            king_stage: KingStage::Shield,
         },
         extra: [0, 0],
         pawn: PieceValues {
@@ -88,6 +198,8 @@ pub fn compile(source: String) -> String {
             attack: 0,
             moves: 0,
         },
+        pawn_structure: None,
+        king_safety: None,
     };
     for line in source.lines() {
         let trimmed = line.trim();
@@ -120,6 +232,46 @@ pub fn compile(source: String) -> String {
                 parsed.state.state_type = StateType::King;
                 continue;
             }
+            "pawnstructure:" => {
+                parsed.state.state_type = StateType::PawnStructure;
+                parsed.pawn_structure.get_or_insert_with(Default::default);
+                continue;
+            }
+            "doubled:" | "isolated:" | "backward:" | "connected:" | "chained:" | "passed:" => {
+                match parsed.state.state_type {
+                    StateType::PawnStructure => {
+                        parsed.state.pawn_stage = match trimmed.to_lowercase().as_str() {
+                            "doubled:" => PawnStage::Doubled,
+                            "isolated:" => PawnStage::Isolated,
+                            "backward:" => PawnStage::Backward,
+                            "connected:" => PawnStage::Connected,
+                            "chained:" => PawnStage::Chained,
+                            _ => PawnStage::Passed,
+                        };
+                        continue;
+                    }
+                    _ => panic!("{trimmed} is outside of the PawnStructure: section"),
+                }
+            }
+            "kingsafety:" => {
+                parsed.state.state_type = StateType::KingSafety;
+                parsed.king_safety.get_or_insert_with(Default::default);
+                continue;
+            }
+            "shield:" | "openfiles:" | "attackers:" | "distance:" => {
+                match parsed.state.state_type {
+                    StateType::KingSafety => {
+                        parsed.state.king_stage = match trimmed.to_lowercase().as_str() {
+                            "shield:" => KingStage::Shield,
+                            "openfiles:" => KingStage::OpenFiles,
+                            "attackers:" => KingStage::Attackers,
+                            _ => KingStage::Distance,
+                        };
+                        continue;
+                    }
+                    _ => panic!("{trimmed} is outside of the KingSafety: section"),
+                }
+            }
             "longcastle:" => match parsed.state.state_type {
                 StateType::Extra => {
                     parsed.state.extra_stage = ExtraStage::LongCastle;
@@ -211,6 +363,22 @@ pub fn compile(source: String) -> String {
                     Stage::Moves => parsed.king.moves = val,
                     Stage::Position => todo!(),
                 },
+                StateType::PawnStructure => {
+                    let values = parsed.pawn_structure.as_mut().unwrap();
+                    match parsed.state.pawn_stage {
+                        PawnStage::Doubled => values.doubled = val,
+                        PawnStage::Isolated => values.isolated = val,
+                        PawnStage::Backward => values.backward = val,
+                        PawnStage::Connected => values.connected = val,
+                        PawnStage::Chained => values.chained = val,
+                        PawnStage::Passed => values.passed.push(val),
+                    }
+                }
+                StateType::KingSafety => parsed
+                    .king_safety
+                    .as_mut()
+                    .unwrap()
+                    .push(&parsed.state.king_stage, val),
             }
             continue;
         }
@@ -230,12 +398,30 @@ pub fn compile(source: String) -> String {
                 panic!("{}", snum);
             };
 
+            // The passed pawn values of all ranks can be on one line
+            if let StateType::PawnStructure = parsed.state.state_type {
+                match parsed.state.pawn_stage {
+                    PawnStage::Passed => parsed.pawn_structure.as_mut().unwrap().passed.push(num),
+                    _ => panic!("{trimmed:?} needs to be a single number, only Passed: has more"),
+                }
+                continue;
+            }
+            // Every king safety field can be on one line
+            if let StateType::KingSafety = parsed.state.state_type {
+                parsed
+                    .king_safety
+                    .as_mut()
+                    .unwrap()
+                    .push(&parsed.state.king_stage, num);
+                continue;
+            }
             match parsed.state.stage_piece {
                 Stage::Position => (),
-                _ => todo!(),
+                _ => panic!("{trimmed:?} needs to be a single number, only Position: has more"),
             }
             match parsed.state.state_type {
-                StateType::Extra => todo!(),
+                StateType::Extra => panic!("{trimmed:?} can't be in the Extra: section"),
+                StateType::PawnStructure | StateType::KingSafety => unreachable!("handled above"),
                 StateType::Pawn => parsed.pawn.position.push(num),
                 StateType::Knight => parsed.knight.position.push(num),
                 StateType::Bishop => parsed.bishop.position.push(num),
@@ -294,6 +480,35 @@ pub fn compile(source: String) -> String {
     bytes.push(parsed.rook.moves);
     bytes.push(parsed.queen.moves);
     bytes.push(parsed.king.moves);
+    if let Some(values) = parsed.pawn_structure {
+        if values.passed.len() != 6 {
+            panic!("Passed: needs a value for each of the ranks 2 to 7");
+        }
+        bytes.push(values.doubled);
+        bytes.push(values.isolated);
+        bytes.push(values.backward);
+        bytes.push(values.connected);
+        bytes.push(values.chained);
+        bytes.extend(values.passed);
+    }
+    if let Some(values) = parsed.king_safety {
+        if bytes.len() == evaluator_0::BYTES {
+            panic!("KingSafety: needs a PawnStructure: section");
+        }
+        if values.shield.len() != 2
+            || values.open_files.len() != 2
+            || values.attackers.len() != 4
+            || values.distance.len() != 4
+        {
+            panic!(
+                "KingSafety: needs 2 Shield:, 2 OpenFiles:, 4 Attackers: and 4 Distance: values"
+            );
+        }
+        bytes.extend(values.shield);
+        bytes.extend(values.open_files);
+        bytes.extend(values.attackers);
+        bytes.extend(values.distance);
+    }
 
     bytes.iter().map(|x| (x + 33) as char).collect::<String>()
 }
@@ -308,3 +523,113 @@ fn decompile(str: &str) {
              
     ";
 }
+
+#[cfg(test)]
+mod tests {
+    use super::compile;
+    use crate::evaluators::{evaluator_0, evaluator_1, evaluator_2};
+
+    /// A plain program where every value is 1
+    fn program() -> String {
+        let mut source = String::from("Extra:\nShortCastle:\n1\nLongCastle:\n1\n");
+        for piece in ["Pawn", "Knight", "Bishop", "Rook", "Queen", "King"] {
+            source += &format!("{piece}:\nBase:\n1\nPosition:\n");
+            source += &"1 1 1 1 1 1 1 1\n".repeat(8);
+            source += "Attack:\n1\nMoves:\n1\n";
+        }
+        source
+    }
+
+    const PAWN_STRUCTURE: &str = "PawnStructure:
+        Doubled:
+          2
+        Isolated:
+          3
+        Backward:
+          4
+        Connected:
+          5
+        Chained:
+          6
+        Passed:
+          1 2 3
+          4
+          5 6";
+
+    #[test]
+    fn pawn_structure() {
+        let plain = compile(program());
+        assert_eq!(plain.len(), evaluator_0::BYTES);
+        let compiled = compile(program() + PAWN_STRUCTURE);
+        assert_eq!(compiled.len(), evaluator_1::BYTES);
+        assert!(compiled.starts_with(&plain));
+        let values = compiled[plain.len()..]
+            .bytes()
+            .map(|b| b - 33)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [2, 3, 4, 5, 6, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Doubled: is outside of the PawnStructure: section")]
+    fn pawn_header_outside_section() {
+        compile(program() + "Doubled:\n2\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "\"2 3\" needs to be a single number, only Passed: has more")]
+    fn several_numbers_outside_passed() {
+        compile(program() + "PawnStructure:\nDoubled:\n2 3\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "Passed: needs a value for each of the ranks 2 to 7")]
+    fn too_few_passed_values() {
+        compile(program() + "PawnStructure:\nPassed:\n1 2 3\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "\"1 1\" can't be in the Extra: section")]
+    fn position_line_in_extra() {
+        compile(program() + "Pawn:\nPosition:\nExtra:\n1 1\n");
+    }
+
+    #[test]
+    fn king_safety() {
+        let pawns = compile(program() + PAWN_STRUCTURE);
+        let compiled = compile(
+            program()
+                + PAWN_STRUCTURE
+                + "
+                KingSafety:
+                  Shield:
+                    3 1
+                  OpenFiles:
+                    2
+                    4
+                  Attackers:
+                    2 2 3 5
+                  Distance:
+                    1 1 1 2",
+        );
+        assert_eq!(compiled.len(), evaluator_2::BYTES);
+        assert!(compiled.starts_with(&pawns));
+        let values = compiled[pawns.len()..]
+            .bytes()
+            .map(|b| b - 33)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [3, 1, 2, 4, 2, 2, 3, 5, 1, 1, 1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Shield: is outside of the KingSafety: section")]
+    fn king_header_outside_section() {
+        compile(program() + PAWN_STRUCTURE + "\nShield:\n3 1");
+    }
+
+    #[test]
+    #[should_panic(expected = "KingSafety: needs a PawnStructure: section")]
+    fn king_safety_without_pawn_structure() {
+        compile(program() + "KingSafety:\nShield:\n3 1");
+    }
+}
@@ -0,0 +1,226 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use chlang::board::{Board, GameState, Player};
+use chlang::cli;
+use chlang::game::{self, Adjudication};
+use chlang::openings;
+use chlang::pool;
+use chlang::tree_evaluator::Bot;
+use chlang::User;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const HELP: &str = "Play bots against each other and write out the positions of the games
+
+Every position a bot searched is written as one line: the FEN, the score of the
+search from white's perspective and the result of the game, separated by |,
+e.g. `<fen> | 12 | 1-0`. Positions where a book move was played are left out.
+
+Usage: datagen --out <FILE> [OPTIONS]
+
+Options:
+  --out <FILE>           File the positions are written to
+  --bot1 <BOT>           First bot [default: DEFAULT]
+  --bot2 <BOT>           Second bot, plays the other color in every game [default: DEFAULT]
+  --games <N>            Number of games [default: 100]
+  --depth <N>            Search depth of both bots, overriding the bot's own
+  --random-plies <N>     Random moves played before the bots take over [default: 8]
+  --openings <FILE>      EPD/FEN file with start positions, a random one is picked every game
+  --resign <SCORE,MOVES> Adjudicate a win when both bots' scores are beyond SCORE for
+                         MOVES moves in a row
+  --draw <SCORE,MOVES,AFTER>
+                         Adjudicate a draw when both bots' scores are within SCORE of 0
+                         for MOVES moves in a row after move AFTER
+  --max-moves <N>        Adjudicate a draw after N moves
+  --concurrency <N>      Number of games played at once [default: 1]
+  --seed <N>             Seed for reproducible data
+  --help                 Print this help";
+
+/// Failed tries at a number of random plies before trying one less
+const TRIES_PER_PLY: usize = 20;
+
+/// Play `plies` random moves. None if the game ended before that.
+fn random_moves(mut board: Board, plies: usize, rng: &mut StdRng) -> Option<Board> {
+    for _ in 0..plies {
+        let moves = board.get_valid_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        board
+            .make_move(&moves[rng.gen_range(0..moves.len())])
+            .ok()?;
+        if board.get_game_state() != GameState::Playing {
+            return None;
+        }
+    }
+    Some(board)
+}
+
+/// Play one game, returning the lines for its positions, the result and
+/// whether it was adjudicated. Fewer random moves are played if the games
+/// keep ending within `plies`.
+fn play(
+    white: &Bot,
+    black: &Bot,
+    start: &[Board],
+    mut plies: usize,
+    adjudication: &Adjudication,
+    rng: &mut StdRng,
+) -> (Vec<String>, GameState, bool) {
+    let mut tries = 0;
+    let board = loop {
+        let opening = start[rng.gen_range(0..start.len())].clone();
+        // Without random moves the opening itself is taken, so this ends
+        if let Some(board) = random_moves(opening, plies, rng) {
+            break board;
+        }
+        tries += 1;
+        if tries % TRIES_PER_PLY == 0 {
+            plies -= 1;
+        }
+    };
+
+    let mut positions = vec![];
+    let (mut wp, mut bp) = (
+        User::Bot(Box::new(white.bot_clone())),
+        User::Bot(Box::new(black.bot_clone())),
+    );
+    let outcome = game::run_with(
+        board,
+        &mut wp,
+        &mut bp,
+        adjudication,
+        None,
+        rng,
+        |board, result| {
+            if result.depth == 0 {
+                return;
+            }
+            let score = match board.turn {
+                Player::White => result.score,
+                Player::Black => -result.score,
+            };
+            positions.push((board.to_fen(), score));
+        },
+    );
+
+    let result = match outcome.state {
+        GameState::Win(Player::White) => "1-0",
+        GameState::Win(Player::Black) => "0-1",
+        _ => "1/2-1/2",
+    };
+    let lines = positions
+        .into_iter()
+        .map(|(fen, score)| format!("{fen} | {score} | {result}"))
+        .collect();
+    (lines, outcome.state, outcome.adjudicated.is_some())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // skip name of program
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if cli::take_switch(&mut args, "--help") {
+        println!("{HELP}");
+        return Ok(());
+    }
+
+    let out = cli::take_flag(&mut args, "--out")?.ok_or("--out is required")?;
+    let mut b1 = cli::bot(
+        cli::take_flag(&mut args, "--bot1")?
+            .as_deref()
+            .unwrap_or("DEFAULT"),
+    )?;
+    let mut b2 = cli::bot(
+        cli::take_flag(&mut args, "--bot2")?
+            .as_deref()
+            .unwrap_or("DEFAULT"),
+    )?;
+    if let Some(depth) = cli::take_parsed::<u8>(&mut args, "--depth")? {
+        b1.search_depth = depth;
+        b2.search_depth = depth;
+    }
+    let games = cli::take_parsed::<usize>(&mut args, "--games")?.unwrap_or(100);
+    let plies = cli::take_parsed::<usize>(&mut args, "--random-plies")?.unwrap_or(8);
+    let start = match cli::take_flag(&mut args, "--openings")? {
+        Some(path) => openings::read(path)?
+            .into_iter()
+            .map(|o| o.board)
+            .collect::<Vec<_>>(),
+        None => vec![Board::default()],
+    };
+    if start.is_empty() {
+        Err("no positions in the openings file")?;
+    }
+    let adjudication = cli::adjudication(&mut args)?;
+    let concurrency = cli::concurrency(&mut args)?;
+    let mut rng = cli::seeded_rng(&mut args)?;
+    cli::finish(&args)?;
+
+    let mut file = BufWriter::new(File::create(&out)?);
+
+    // Bot 1 plays white in even games. The seeds are drawn up front so the
+    // games don't depend on the order they finish in.
+    let jobs = (0..games)
+        .map(|i| (i % 2 == 1, rng.gen::<u64>()))
+        .collect::<Vec<_>>();
+    let mut written = Ok(());
+    let (mut positions, mut finished) = (0, 0);
+    let mut wins = [0; 3];
+    let mut adjudicated = 0;
+    let (b1, b2, start, adjudication) = (&b1, &b2, &start, &adjudication);
+    pool::run(
+        jobs,
+        concurrency,
+        |(swapped, seed)| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            match swapped {
+                false => play(b1, b2, start, plies, adjudication, &mut rng),
+                true => play(b2, b1, start, plies, adjudication, &mut rng),
+            }
+        },
+        |(lines, state, was_adjudicated)| {
+            adjudicated += was_adjudicated as usize;
+            match state {
+                GameState::Win(Player::White) => wins[0] += 1,
+                GameState::Win(Player::Black) => wins[1] += 1,
+                _ => wins[2] += 1,
+            }
+            finished += 1;
+            positions += lines.len();
+            if written.is_ok() {
+                written = lines.iter().try_for_each(|l| writeln!(file, "{l}"));
+            }
+            println!("game {finished}/{games}: {positions} positions");
+            // Stop if the file can't be written to
+            written.is_ok()
+        },
+    );
+    written?;
+    file.flush()?;
+
+    println!(
+        "White wins: {}\nBlack wins: {}\nDraws: {}\nAdjudicated: {adjudicated}\nPositions: {positions}",
+        wins[0], wins[1], wins[2]
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chlang::evaluators::material_evaluator::MaterialEvaluator;
+
+    use super::*;
+
+    #[test]
+    fn opening_that_always_ends() {
+        let stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let bot = Bot::new(Box::new(MaterialEvaluator::default()), 1);
+        let mut rng = StdRng::seed_from_u64(0);
+        let adjudication = Adjudication::default();
+        let (lines, state, adjudicated) =
+            play(&bot, &bot, &[stalemate], 8, &adjudication, &mut rng);
+        assert!(lines.is_empty());
+        assert_eq!(state, GameState::Draw);
+        assert!(!adjudicated);
+    }
+}
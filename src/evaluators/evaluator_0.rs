@@ -1,16 +1,22 @@
 use std::{
     io::{BufRead, Read},
-    ops::Deref,
+    ops::{Deref, Range},
     str::FromStr,
 };
 
-use rand::Rng;
+use rand::{rngs::StdRng, Rng};
 
 use crate::{
-    board::{Player, Position},
+    board::{Board, Player, Position},
     tree_evaluator::Eval,
 };
 
+/// Lengths of the fields in the string representation, in order: piece values,
+/// positional values, attack values, castle bonuses and move values
+pub const SECTIONS: [usize; 5] = [6, 6 * 64, 6, 2, 6];
+/// Length of the string representation
+pub const BYTES: usize = 6 + 6 * 64 + 6 + 2 + 6;
+
 #[derive(Clone, Debug)]
 pub struct Evaluator {
     piece_values: [u8; 6],
@@ -132,9 +138,12 @@ impl From<&[u8]> for Evaluator {
 impl FromStr for Evaluator {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() < BYTES {
+            return Err("string representation is too short");
+        }
         Ok(s.chars()
             .map(|x| match x.is_ascii() {
-                true => Ok(x as u8 - 33),
+                true if x >= '!' => Ok(x as u8 - 33),
                 _ => return Err("should be valid ascii"),
             })
             .collect::<Result<Vec<_>, _>>()?
@@ -170,6 +179,60 @@ impl From<Evaluator> for String {
     }
 }
 
+/// Ranges of the fields crossover keeps together, the position table of each
+/// piece is a field of its own
+pub fn fields() -> Vec<Range<usize>> {
+    let mut fields = vec![];
+    let mut start = 0;
+    for (i, length) in SECTIONS.iter().enumerate() {
+        let parts = if i == 1 { 6 } else { 1 };
+        for _ in 0..parts {
+            fields.push(start..start + length / parts);
+            start += length / parts;
+        }
+    }
+    fields
+}
+
+/// How often every byte of the representation counts in the evaluation of the
+/// board, from white's perspective. The evaluation is linear in the bytes, so
+/// `evaluate` is the sum of every feature times its byte.
+pub fn features(board: &mut Board) -> [isize; BYTES] {
+    let attacks = 6 + 6 * 64;
+    let castle = attacks + 6;
+    let moves = castle + 2;
+    let mut features = [0; BYTES];
+    for piece_index in 0..6 {
+        let white_pieces = board.white_piece_bitboard & board.piece_bitboards[piece_index];
+        let black_pieces = board.black_piece_bitboard & board.piece_bitboards[piece_index];
+        features[piece_index] += white_pieces.count_ones() as isize;
+        features[piece_index] -= black_pieces.count_ones() as isize;
+        for i in 0..64 {
+            let (sign, square) = if (1 << i) & white_pieces != 0 {
+                (1, i)
+            } else if (1 << i) & black_pieces != 0 {
+                (-1, 63 - i)
+            } else {
+                continue;
+            };
+            let pos = Position::from(1 << i);
+            features[6 + piece_index * 64 + square] += sign;
+            features[attacks + piece_index] += board
+                .number_of_attacks_by_color(&pos, &Player::White)
+                - board.number_of_attacks_by_color(&pos, &Player::Black);
+            features[moves + piece_index] +=
+                sign * board.get_valid_moves_from_pos(&pos).len() as isize;
+        }
+    }
+    for (i, can_castle) in [board.can_castle_short, board.can_castle_long]
+        .iter()
+        .enumerate()
+    {
+        features[castle + i] = can_castle[1] as isize - can_castle[0] as isize;
+    }
+    features
+}
+
 impl Eval for Evaluator {
     fn evaluate(&self, board: &mut crate::board::Board) -> isize {
         let mut value = 0;
@@ -228,23 +291,59 @@ impl Eval for Evaluator {
         value
     }
 
-    fn modified(&self) -> Box<dyn Eval + Sync + Send> {
+    fn modified(&self, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
         let temp = self.clone();
         let string = String::from(temp);
         let bytes = string.as_bytes();
         let mut new_bytes = vec![];
         for b in bytes {
             let nb = b - 33;
-            if rand::thread_rng().gen_bool(1.0 / 5.0) {
-                let newval = (nb as isize + rand::thread_rng().gen_range(-5..=5));
+            if rng.gen_bool(1.0 / 5.0) {
+                let newval = (nb as isize + rng.gen_range(-5..=5));
                 let newval = newval.max(0).min(93) as u8;
-                new_bytes.push(newval + 33);
+                new_bytes.push(newval);
             } else {
-                new_bytes.push(nb + 33);
+                new_bytes.push(nb);
             }
         }
         return Box::new(Evaluator::from(new_bytes.as_slice()));
     }
+    /// Every field comes from one of the parents as a whole, the position
+    /// table of each piece counts as a field of its own
+    fn crossover(&self, other: &dyn Eval, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        // from_str ignores anything after the first BYTES, so a longer
+        // string belongs to another kind of evaluator
+        let other = other.string_rep();
+        if other.len() != BYTES {
+            return Box::new(self.clone());
+        }
+        let Ok(other) = Evaluator::from_str(&other) else {
+            return Box::new(self.clone());
+        };
+        let mut child = self.clone();
+        if rng.gen_bool(0.5) {
+            child.piece_values = other.piece_values;
+        }
+        for (table, other_table) in child
+            .piece_positional_values
+            .iter_mut()
+            .zip(other.piece_positional_values)
+        {
+            if rng.gen_bool(0.5) {
+                *table = other_table;
+            }
+        }
+        if rng.gen_bool(0.5) {
+            child.piece_attack_values = other.piece_attack_values;
+        }
+        if rng.gen_bool(0.5) {
+            child.castle_bonus = other.castle_bonus;
+        }
+        if rng.gen_bool(0.5) {
+            child.piece_moves_values = other.piece_moves_values;
+        }
+        Box::new(child)
+    }
     fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
         Box::new(self.clone())
     }
@@ -252,3 +351,16 @@ impl Eval for Evaluator {
         String::from(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutations_stay_printable() {
+        crate::evaluators::tests::mutations_stay_printable(
+            |s| Evaluator::from_str(s).unwrap(),
+            BYTES,
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::str::FromStr;
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{board::Board, evaluators::evaluator_0, tree_evaluator::Eval};
+
+/// Lengths of the pawn structure fields that follow the `evaluator_0` ones, in
+/// order: doubled, isolated, backward, connected and chained pawns, and passed
+/// pawns on the ranks 2 to 7 from the side of their color
+pub const PAWN_SECTIONS: [usize; 6] = [1, 1, 1, 1, 1, 6];
+/// Length of the pawn structure fields
+pub const PAWN_BYTES: usize = 1 + 1 + 1 + 1 + 1 + 6;
+/// Length of the string representation
+pub const BYTES: usize = evaluator_0::BYTES + PAWN_BYTES;
+
+const DOUBLED: usize = 0;
+const ISOLATED: usize = 1;
+const BACKWARD: usize = 2;
+const CONNECTED: usize = 3;
+const CHAINED: usize = 4;
+const PASSED: usize = 5;
+
+/// `evaluator_0` with values for the pawn structure. Doubled, isolated and
+/// backward pawns are penalties, connected, chained and passed pawns bonuses.
+#[derive(Clone, Debug)]
+pub struct Evaluator {
+    base: [u8; evaluator_0::BYTES],
+    pawns: [u8; PAWN_BYTES],
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        let mut base = [0; evaluator_0::BYTES];
+        for (b, c) in base
+            .iter_mut()
+            .zip(String::from(evaluator_0::Evaluator::default()).bytes())
+        {
+            *b = c - 33;
+        }
+        Self {
+            base,
+            pawns: [3, 3, 2, 1, 1, 1, 1, 2, 4, 7, 11],
+        }
+    }
+}
+
+impl From<&[u8]> for Evaluator {
+    fn from(value: &[u8]) -> Self {
+        Self {
+            base: value[..evaluator_0::BYTES].try_into().unwrap(),
+            pawns: value[evaluator_0::BYTES..BYTES].try_into().unwrap(),
+        }
+    }
+}
+
+impl FromStr for Evaluator {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != BYTES {
+            return Err("string representation of evaluator_1 has the wrong length");
+        }
+        Ok(s.chars()
+            .map(|x| match x {
+                '!'..='~' => Ok(x as u8 - 33),
+                _ => Err("should be valid ascii"),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .as_slice()
+            .into())
+    }
+}
+
+impl From<Evaluator> for String {
+    fn from(value: Evaluator) -> Self {
+        value
+            .base
+            .iter()
+            .chain(&value.pawns)
+            .map(|b| (b + 33) as char)
+            .collect()
+    }
+}
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+pub(crate) fn file(col: u32) -> u64 {
+    FILE_A << col
+}
+
+/// The files left and right of `col`
+pub(crate) fn adjacent_files(col: u32) -> u64 {
+    let left = if col > 0 { file(col - 1) } else { 0 };
+    let right = if col < 7 { file(col + 1) } else { 0 };
+    left | right
+}
+
+/// Row `row`, empty outside of the board
+pub(crate) fn rank(row: u32) -> u64 {
+    if row < 8 {
+        0xFF << (8 * row)
+    } else {
+        0
+    }
+}
+
+/// The rows in front of `row` from white's side
+fn rows_above(row: u32) -> u64 {
+    if row < 7 {
+        !0 << (8 * (row + 1))
+    } else {
+        0
+    }
+}
+
+/// Pawn structure of the pawns `own` against the pawns `enemy`, both seen
+/// from white's side: how many pawns are doubled, isolated and so on, laid
+/// out like the pawn structure bytes
+fn pawn_counts(own: u64, enemy: u64) -> [isize; PAWN_BYTES] {
+    let mut counts = [0; PAWN_BYTES];
+    for col in 0..8 {
+        counts[DOUBLED] += (own & file(col)).count_ones().saturating_sub(1) as isize;
+    }
+    let mut pawns = own;
+    while pawns != 0 {
+        let square = pawns.trailing_zeros();
+        pawns &= pawns - 1;
+        let (row, col) = (square / 8, square % 8);
+        let neighbours = own & adjacent_files(col);
+
+        let isolated = neighbours == 0;
+        counts[ISOLATED] += isolated as isize;
+        // No pawn beside or behind it can ever defend it, and an enemy pawn
+        // stops it from moving up to the others
+        let stopped = enemy & adjacent_files(col) & rank(row + 2) != 0;
+        counts[BACKWARD] += (!isolated && neighbours & !rows_above(row) == 0 && stopped) as isize;
+        counts[CONNECTED] += (neighbours & rank(row) != 0) as isize;
+        counts[CHAINED] += (row > 0 && neighbours & rank(row - 1) != 0) as isize;
+        let passed = enemy & (file(col) | adjacent_files(col)) & rows_above(row) == 0;
+        if passed && (1..=6).contains(&row) {
+            counts[PASSED + row as usize - 1] += 1;
+        }
+    }
+    counts
+}
+
+/// How often every pawn structure byte counts in the evaluation of the board,
+/// from white's perspective. Penalties count negatively.
+pub fn pawn_features(board: &Board) -> [isize; PAWN_BYTES] {
+    let pawns = board.piece_bitboards[0];
+    let (white, black) = (
+        pawns & board.white_piece_bitboard,
+        pawns & board.black_piece_bitboard,
+    );
+    // Flipping the rows lets black's pawns be counted like white's
+    let white_counts = pawn_counts(white, black);
+    let black_counts = pawn_counts(black.swap_bytes(), white.swap_bytes());
+    let mut features = [0; PAWN_BYTES];
+    for (i, feature) in features.iter_mut().enumerate() {
+        let sign = if i < CONNECTED { -1 } else { 1 };
+        *feature = sign * (white_counts[i] - black_counts[i]);
+    }
+    features
+}
+
+/// How often every byte of the representation counts in the evaluation of the
+/// board, from white's perspective, like `evaluator_0::features`
+pub fn features(board: &mut Board) -> [isize; BYTES] {
+    let mut features = [0; BYTES];
+    features[..evaluator_0::BYTES].copy_from_slice(&evaluator_0::features(board));
+    features[evaluator_0::BYTES..].copy_from_slice(&pawn_features(board));
+    features
+}
+
+/// Ranges of the fields crossover keeps together
+pub fn fields() -> Vec<std::ops::Range<usize>> {
+    let mut fields = evaluator_0::fields();
+    let mut start = evaluator_0::BYTES;
+    for length in PAWN_SECTIONS {
+        fields.push(start..start + length);
+        start += length;
+    }
+    fields
+}
+
+impl Eval for Evaluator {
+    fn evaluate(&self, board: &mut Board) -> isize {
+        features(board)
+            .iter()
+            .zip(self.base.iter().chain(&self.pawns))
+            .map(|(feature, value)| feature * *value as isize)
+            .sum()
+    }
+
+    fn modified(&self, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        let mut child = self.clone();
+        for b in child.base.iter_mut().chain(child.pawns.iter_mut()) {
+            if rng.gen_bool(1.0 / 5.0) {
+                *b = (*b as isize + rng.gen_range(-5..=5)).clamp(0, 93) as u8;
+            }
+        }
+        Box::new(child)
+    }
+
+    /// Every field comes from one of the parents as a whole
+    fn crossover(&self, other: &dyn Eval, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        let Ok(other) = Evaluator::from_str(&other.string_rep()) else {
+            return Box::new(self.clone());
+        };
+        let mut values = self
+            .base
+            .iter()
+            .chain(&self.pawns)
+            .copied()
+            .collect::<Vec<_>>();
+        let other_values = other
+            .base
+            .iter()
+            .chain(&other.pawns)
+            .copied()
+            .collect::<Vec<_>>();
+        for field in fields() {
+            if rng.gen_bool(0.5) {
+                values[field.clone()].copy_from_slice(&other_values[field]);
+            }
+        }
+        Box::new(Evaluator::from(values.as_slice()))
+    }
+
+    fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
+        Box::new(self.clone())
+    }
+
+    fn string_rep(&self) -> String {
+        String::from(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutations_stay_printable() {
+        crate::evaluators::tests::mutations_stay_printable(
+            |s| Evaluator::from_str(s).unwrap(),
+            BYTES,
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::str::FromStr;
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    board::Board,
+    evaluators::evaluator_1::{self, adjacent_files, file, rank},
+    tree_evaluator::Eval,
+};
+
+/// Lengths of the king safety fields that follow the `evaluator_1` ones, in
+/// order: shield pawns one and two rows in front of the king, semi-open and
+/// open files next to it, enemy knights, bishops, rooks and queens attacking
+/// the squares around it, and how close the same pieces are to it
+pub const KING_SECTIONS: [usize; 4] = [2, 2, 4, 4];
+/// Length of the king safety fields
+pub const KING_BYTES: usize = 2 + 2 + 4 + 4;
+/// Length of the string representation
+pub const BYTES: usize = evaluator_1::BYTES + KING_BYTES;
+
+const SHIELD: usize = 0;
+const OPEN_FILES: usize = 2;
+const ATTACKERS: usize = 4;
+const DISTANCE: usize = 8;
+
+const KNIGHT_JUMPS: [(i32, i32); 8] = [
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+    (1, 2),
+    (-1, 2),
+    (1, -2),
+    (-1, -2),
+];
+const DIAGONALS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const LINES: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// `evaluator_1` with values for the safety of the king. The pawn shield is a
+/// bonus, open files, attackers and nearby enemy pieces are penalties.
+#[derive(Clone, Debug)]
+pub struct Evaluator {
+    base: [u8; evaluator_1::BYTES],
+    king: [u8; KING_BYTES],
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        let mut base = [0; evaluator_1::BYTES];
+        for (b, c) in base
+            .iter_mut()
+            .zip(String::from(evaluator_1::Evaluator::default()).bytes())
+        {
+            *b = c - 33;
+        }
+        Self {
+            base,
+            king: [3, 1, 2, 4, 2, 2, 3, 5, 1, 1, 1, 2],
+        }
+    }
+}
+
+impl From<&[u8]> for Evaluator {
+    fn from(value: &[u8]) -> Self {
+        Self {
+            base: value[..evaluator_1::BYTES].try_into().unwrap(),
+            king: value[evaluator_1::BYTES..BYTES].try_into().unwrap(),
+        }
+    }
+}
+
+impl FromStr for Evaluator {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != BYTES {
+            return Err("string representation of evaluator_2 has the wrong length");
+        }
+        Ok(s.chars()
+            .map(|x| match x {
+                '!'..='~' => Ok(x as u8 - 33),
+                _ => Err("should be valid ascii"),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .as_slice()
+            .into())
+    }
+}
+
+impl From<Evaluator> for String {
+    fn from(value: Evaluator) -> Self {
+        value
+            .base
+            .iter()
+            .chain(&value.king)
+            .map(|b| (b + 33) as char)
+            .collect()
+    }
+}
+
+/// Squares reached by stepping from `square` in each of `directions`, up to and
+/// including the first occupied square unless `slides` is false
+fn reach(square: u32, directions: &[(i32, i32)], slides: bool, occupied: u64) -> u64 {
+    let mut reached = 0;
+    for (r, c) in directions {
+        let (mut row, mut col) = (square as i32 / 8 + r, square as i32 % 8 + c);
+        while (0..8).contains(&row) && (0..8).contains(&col) {
+            let bit = 1 << (row * 8 + col);
+            reached |= bit;
+            if !slides || occupied & bit != 0 {
+                break;
+            }
+            row += r;
+            col += c;
+        }
+    }
+    reached
+}
+
+/// Squares attacked by a knight, bishop, rook or queen (`piece` 0 to 3) on
+/// `square`
+fn attacks(piece: usize, square: u32, occupied: u64) -> u64 {
+    match piece {
+        0 => reach(square, &KNIGHT_JUMPS, false, occupied),
+        1 => reach(square, &DIAGONALS, true, occupied),
+        2 => reach(square, &LINES, true, occupied),
+        _ => reach(square, &DIAGONALS, true, occupied) | reach(square, &LINES, true, occupied),
+    }
+}
+
+/// King safety of the king on `king` against the enemy knights, bishops, rooks
+/// and queens in `enemy`, everything seen from white's side, laid out like the
+/// king safety bytes
+fn king_counts(
+    king: u32,
+    own_pawns: u64,
+    enemy_pawns: u64,
+    enemy: [u64; 4],
+    occupied: u64,
+) -> [isize; KING_BYTES] {
+    let mut counts = [0; KING_BYTES];
+    let (row, col) = (king / 8, king % 8);
+    let files = file(col) | adjacent_files(col);
+
+    counts[SHIELD] = (own_pawns & files & rank(row + 1)).count_ones() as isize;
+    counts[SHIELD + 1] = (own_pawns & files & rank(row + 2)).count_ones() as isize;
+    for f in col.saturating_sub(1)..=(col + 1).min(7) {
+        if own_pawns & file(f) == 0 {
+            let open = enemy_pawns & file(f) == 0;
+            counts[OPEN_FILES + open as usize] += 1;
+        }
+    }
+
+    // The king's square and the squares around it
+    let zone = reach(king, &DIAGONALS, false, 0) | reach(king, &LINES, false, 0) | 1 << king;
+    for (piece, bitboard) in enemy.iter().enumerate() {
+        let mut pieces = *bitboard;
+        while pieces != 0 {
+            let square = pieces.trailing_zeros();
+            pieces &= pieces - 1;
+            counts[ATTACKERS + piece] += (attacks(piece, square, occupied) & zone != 0) as isize;
+            let distance = (row.abs_diff(square / 8)).max(col.abs_diff(square % 8));
+            counts[DISTANCE + piece] += 7 - distance as isize;
+        }
+    }
+    counts
+}
+
+/// How often every king safety byte counts in the evaluation of the board,
+/// from white's perspective. Penalties count negatively.
+pub fn king_features(board: &Board) -> [isize; KING_BYTES] {
+    let (white, black) = (board.white_piece_bitboard, board.black_piece_bitboard);
+    let occupied = white | black;
+    let pieces =
+        |color: u64| [1, 2, 3, 4].map(|piece_index| board.piece_bitboards[piece_index] & color);
+    let (kings, pawns) = (board.piece_bitboards[5], board.piece_bitboards[0]);
+    if kings & white == 0 || kings & black == 0 {
+        return [0; KING_BYTES];
+    }
+    // Flipping the rows lets black's king be looked at like white's
+    let white_counts = king_counts(
+        (kings & white).trailing_zeros(),
+        pawns & white,
+        pawns & black,
+        pieces(black),
+        occupied,
+    );
+    let black_counts = king_counts(
+        (kings & black).swap_bytes().trailing_zeros(),
+        (pawns & black).swap_bytes(),
+        (pawns & white).swap_bytes(),
+        pieces(white).map(u64::swap_bytes),
+        occupied.swap_bytes(),
+    );
+    let mut features = [0; KING_BYTES];
+    for (i, feature) in features.iter_mut().enumerate() {
+        let sign = if i < OPEN_FILES { 1 } else { -1 };
+        *feature = sign * (white_counts[i] - black_counts[i]);
+    }
+    features
+}
+
+/// How often every byte of the representation counts in the evaluation of the
+/// board, from white's perspective, like `evaluator_1::features`
+pub fn features(board: &mut Board) -> [isize; BYTES] {
+    let mut features = [0; BYTES];
+    features[..evaluator_1::BYTES].copy_from_slice(&evaluator_1::features(board));
+    features[evaluator_1::BYTES..].copy_from_slice(&king_features(board));
+    features
+}
+
+/// Ranges of the fields crossover keeps together
+fn fields() -> Vec<std::ops::Range<usize>> {
+    let mut fields = evaluator_1::fields();
+    let mut start = evaluator_1::BYTES;
+    for length in KING_SECTIONS {
+        fields.push(start..start + length);
+        start += length;
+    }
+    fields
+}
+
+impl Eval for Evaluator {
+    fn evaluate(&self, board: &mut Board) -> isize {
+        features(board)
+            .iter()
+            .zip(self.base.iter().chain(&self.king))
+            .map(|(feature, value)| feature * *value as isize)
+            .sum()
+    }
+
+    fn modified(&self, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        let mut child = self.clone();
+        for b in child.base.iter_mut().chain(child.king.iter_mut()) {
+            if rng.gen_bool(1.0 / 5.0) {
+                *b = (*b as isize + rng.gen_range(-5..=5)).clamp(0, 93) as u8;
+            }
+        }
+        Box::new(child)
+    }
+
+    /// Every field comes from one of the parents as a whole
+    fn crossover(&self, other: &dyn Eval, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        let Ok(other) = Evaluator::from_str(&other.string_rep()) else {
+            return Box::new(self.clone());
+        };
+        let mut values = self
+            .base
+            .iter()
+            .chain(&self.king)
+            .copied()
+            .collect::<Vec<_>>();
+        let other_values = other
+            .base
+            .iter()
+            .chain(&other.king)
+            .copied()
+            .collect::<Vec<_>>();
+        for field in fields() {
+            if rng.gen_bool(0.5) {
+                values[field.clone()].copy_from_slice(&other_values[field]);
+            }
+        }
+        Box::new(Evaluator::from(values.as_slice()))
+    }
+
+    fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
+        Box::new(self.clone())
+    }
+
+    fn string_rep(&self) -> String {
+        String::from(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutations_stay_printable() {
+        crate::evaluators::tests::mutations_stay_printable(
+            |s| Evaluator::from_str(s).unwrap(),
+            BYTES,
+        );
+    }
+
+    /// White's king has a full shield and a knight next to it, black's king
+    /// only the g pawn, two semi-open files and a rook attacking f7
+    #[test]
+    fn hand_counted_king_features() {
+        let board = Board::from_fen("6k1/R5p1/8/8/8/4n2P/5PP1/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            king_features(&board),
+            [
+                // Shield: white f2 g2 and h3, black g7
+                1, 1, //
+                // OpenFiles: black's f and h files only have white pawns
+                2, 0, //
+                // Attackers: the knight reaches g2, the rook f7
+                -1, 0, 1, 0, //
+                // Distance: 7 - 2 for the knight, 7 - 6 for the rook
+                -5, 0, 1, 0,
+            ]
+        );
+    }
+}
@@ -3,10 +3,13 @@ use std::{
     str::FromStr,
 };
 
-use rand::Rng;
+use rand::{rngs::StdRng, Rng};
 
 use crate::tree_evaluator::Eval;
 
+/// Length of the string representation: piece values
+pub const BYTES: usize = 6;
+
 #[derive(Clone)]
 pub struct MaterialEvaluator {
     piece_values: [u8; 6],
@@ -31,6 +34,9 @@ impl From<&[u8]> for MaterialEvaluator {
 impl FromStr for MaterialEvaluator {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() < BYTES {
+            return Err("string representation is too short");
+        }
         Ok(s.chars()
             .map(|x| match x.is_ascii() {
                 true => Ok(x as u8),
@@ -61,15 +67,15 @@ impl Eval for MaterialEvaluator {
         }
         value
     }
-    fn modified(&self) -> Box<dyn Eval + Sync + Send> {
+    fn modified(&self, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
         let temp = self.clone();
         let string = String::from(temp);
         let bytes = string.as_bytes();
         let mut new_bytes = vec![];
         for b in bytes {
-            if rand::thread_rng().gen_bool(1.0 / 10.0) {
-                let newval =
-                    ((*b as isize + 128 + rand::thread_rng().gen_range(-1..=1)) % 128) as u8;
+            if rng.gen_bool(1.0 / 10.0) {
+                // Clamped instead of wrapped around, so the string stays printable
+                let newval = (*b as isize + rng.gen_range(-1..=1)).clamp(33, 126) as u8;
                 new_bytes.push(newval);
             } else {
                 new_bytes.push(*b);
@@ -78,6 +84,25 @@ impl Eval for MaterialEvaluator {
         let new_s = String::from_utf8(new_bytes).unwrap();
         return Box::new(MaterialEvaluator::from_str(&new_s).unwrap());
     }
+    /// There is only one field, so every piece value is inherited on its own
+    fn crossover(&self, other: &dyn Eval, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        // from_str ignores anything after the first BYTES, so a longer
+        // string belongs to another kind of evaluator
+        let other = other.string_rep();
+        if other.len() != BYTES {
+            return Box::new(self.clone());
+        }
+        let Ok(other) = MaterialEvaluator::from_str(&other) else {
+            return Box::new(self.clone());
+        };
+        let mut child = self.clone();
+        for (value, other_value) in child.piece_values.iter_mut().zip(other.piece_values) {
+            if rng.gen_bool(0.5) {
+                *value = other_value;
+            }
+        }
+        Box::new(child)
+    }
     fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
         Box::new(self.clone())
     }
@@ -85,3 +110,16 @@ impl Eval for MaterialEvaluator {
         String::from(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutations_stay_printable() {
+        crate::evaluators::tests::mutations_stay_printable(
+            |s| MaterialEvaluator::from_str(s).unwrap(),
+            BYTES,
+        );
+    }
+}
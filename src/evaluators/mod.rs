@@ -1,11 +1,19 @@
+use rand::rngs::StdRng;
+
 use crate::tree_evaluator::{self, Eval};
 
 pub mod material_evaluator;
 
 pub mod evaluator_0;
 
+pub mod evaluator_1;
+
+pub mod evaluator_2;
+
 pub mod positional_evaluator;
 
+pub mod tapered_evaluator;
+
 #[derive(Clone)]
 pub struct NoneEvaluator;
 
@@ -13,9 +21,12 @@ impl tree_evaluator::Eval for NoneEvaluator {
     fn evaluate(&self, board: &mut crate::board::Board) -> isize {
         return 0;
     }
-    fn modified(&self) -> Box<dyn Eval + Sync + Send> {
+    fn modified(&self, _rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
         return Box::new(self.clone());
     }
+    fn crossover(&self, _other: &dyn Eval, _rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        Box::new(self.clone())
+    }
     fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
         Box::new(self.clone())
     }
@@ -23,3 +34,63 @@ impl tree_evaluator::Eval for NoneEvaluator {
         String::from("")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// Mutate an evaluator with every byte at the lowest or the highest
+    /// printable character many times, its string must stay printable
+    pub(super) fn mutations_stay_printable<E>(from_str: impl Fn(&str) -> E, bytes: usize)
+    where
+        E: Eval + Sync + Send + 'static,
+    {
+        let mut rng = StdRng::seed_from_u64(0);
+        for edge in ['!', '~'] {
+            let mut evaluator: Box<dyn Eval + Sync + Send> =
+                Box::new(from_str(&edge.to_string().repeat(bytes)));
+            for _ in 0..1000 {
+                evaluator = evaluator.modified(&mut rng);
+                let rep = evaluator.string_rep();
+                assert!(rep.bytes().all(|b| (33..=126).contains(&b)), "{rep:?}");
+            }
+        }
+    }
+
+    /// Crossing evaluators of different kinds keeps the first one as it is
+    #[test]
+    fn crossover_needs_same_kind() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // Every kind gets its own character, so a mixed child would show
+        let evaluators: Vec<Box<dyn Eval + Sync + Send>> = vec![
+            Box::new(
+                material_evaluator::MaterialEvaluator::from_str(
+                    &"a".repeat(material_evaluator::BYTES),
+                )
+                .unwrap(),
+            ),
+            Box::new(
+                positional_evaluator::PositionalEvaluator::from_str(
+                    &"b".repeat(positional_evaluator::BYTES),
+                )
+                .unwrap(),
+            ),
+            Box::new(evaluator_0::Evaluator::from_str(&"c".repeat(evaluator_0::BYTES)).unwrap()),
+            Box::new(evaluator_1::Evaluator::from_str(&"d".repeat(evaluator_1::BYTES)).unwrap()),
+            Box::new(evaluator_2::Evaluator::from_str(&"e".repeat(evaluator_2::BYTES)).unwrap()),
+            Box::new(NoneEvaluator),
+        ];
+        for (i, a) in evaluators.iter().enumerate() {
+            for (j, b) in evaluators.iter().enumerate() {
+                if i != j {
+                    let child = a.crossover(b.as_ref(), &mut rng);
+                    assert_eq!(child.string_rep(), a.string_rep(), "{i} x {j}");
+                }
+            }
+        }
+    }
+}
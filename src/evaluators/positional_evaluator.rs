@@ -3,10 +3,13 @@ use std::{
     str::FromStr,
 };
 
-use rand::Rng;
+use rand::{rngs::StdRng, Rng};
 
 use crate::tree_evaluator::Eval;
 
+/// Length of the string representation: piece values and positional values
+pub const BYTES: usize = 6 + 6 * 64;
+
 #[derive(Clone)]
 pub struct PositionalEvaluator {
     piece_values: [u8; 6],
@@ -112,6 +115,9 @@ impl From<&[u8]> for PositionalEvaluator {
 impl FromStr for PositionalEvaluator {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() < BYTES {
+            return Err("string representation is too short");
+        }
         Ok(s.chars()
             .map(|x| match x.is_ascii() {
                 true => Ok(x as u8),
@@ -161,15 +167,15 @@ impl Eval for PositionalEvaluator {
         }
         value
     }
-    fn modified(&self) -> Box<dyn Eval + Sync + Send> {
+    fn modified(&self, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
         let temp = self.clone();
         let string = String::from(temp);
         let bytes = string.as_bytes();
         let mut new_bytes = vec![];
         for b in bytes {
-            if rand::thread_rng().gen_bool(1.0 / 10.0) {
-                let newval =
-                    ((*b as isize + 128 + rand::thread_rng().gen_range(-1..=1)) % 128) as u8;
+            if rng.gen_bool(1.0 / 10.0) {
+                // Clamped instead of wrapped around, so the string stays printable
+                let newval = (*b as isize + rng.gen_range(-1..=1)).clamp(33, 126) as u8;
                 new_bytes.push(newval);
             } else {
                 new_bytes.push(*b);
@@ -178,6 +184,33 @@ impl Eval for PositionalEvaluator {
         let new_s = String::from_utf8(new_bytes).unwrap();
         return Box::new(PositionalEvaluator::from_str(&new_s).unwrap());
     }
+    /// The piece values come from one of the parents as a whole, and so
+    /// does the position table of each piece
+    fn crossover(&self, other: &dyn Eval, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        // from_str ignores anything after the first BYTES, so a longer
+        // string belongs to another kind of evaluator
+        let other = other.string_rep();
+        if other.len() != BYTES {
+            return Box::new(self.clone());
+        }
+        let Ok(other) = PositionalEvaluator::from_str(&other) else {
+            return Box::new(self.clone());
+        };
+        let mut child = self.clone();
+        if rng.gen_bool(0.5) {
+            child.piece_values = other.piece_values;
+        }
+        for (table, other_table) in child
+            .piece_positional_values
+            .iter_mut()
+            .zip(other.piece_positional_values)
+        {
+            if rng.gen_bool(0.5) {
+                *table = other_table;
+            }
+        }
+        Box::new(child)
+    }
     fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
         Box::new(self.clone())
     }
@@ -185,3 +218,16 @@ impl Eval for PositionalEvaluator {
         String::from(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutations_stay_printable() {
+        crate::evaluators::tests::mutations_stay_printable(
+            |s| PositionalEvaluator::from_str(s).unwrap(),
+            BYTES,
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::str::FromStr;
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    board::Board,
+    evaluators::evaluator_0::{self, SECTIONS},
+    tree_evaluator::Eval,
+};
+
+/// Length of the string representation: the midgame values, then the endgame
+/// values, both laid out like `evaluator_0`
+pub const BYTES: usize = 2 * evaluator_0::BYTES;
+
+/// How much every piece counts towards the game phase
+const PHASE_WEIGHTS: [isize; 6] = [0, 1, 1, 2, 4, 0];
+/// Phase of the start position, all pieces but pawns and kings on the board
+pub const MAX_PHASE: isize = 24;
+
+/// `evaluator_0` with separate values for the midgame and the endgame. The two
+/// evaluations are blended by the game phase, which goes from `MAX_PHASE` in
+/// the start position down to 0 when only pawns and kings are left.
+#[derive(Clone, Debug)]
+pub struct TaperedEvaluator {
+    midgame: [u8; evaluator_0::BYTES],
+    endgame: [u8; evaluator_0::BYTES],
+}
+
+/// Bytes of an `evaluator_0` evaluator
+fn bytes(evaluator: evaluator_0::Evaluator) -> [u8; evaluator_0::BYTES] {
+    let mut bytes = [0; evaluator_0::BYTES];
+    for (b, c) in bytes.iter_mut().zip(String::from(evaluator).bytes()) {
+        *b = c - 33;
+    }
+    bytes
+}
+
+impl Default for TaperedEvaluator {
+    /// The default `evaluator_0` values in the midgame. In the endgame the king
+    /// moves to the center instead of hiding in the corner, and castling
+    /// rights are worth nothing.
+    fn default() -> Self {
+        let midgame = bytes(evaluator_0::Evaluator::default());
+        let mut endgame = midgame;
+        let king = SECTIONS[0] + 5 * 64;
+        for (i, value) in endgame[king..king + 64].iter_mut().enumerate() {
+            let (row, col) = ((i / 8) as u8, (i % 8) as u8);
+            // Distance to the edges, 0 in the corners and 6 in the center
+            *value = row.min(7 - row) + col.min(7 - col);
+        }
+        let castle = SECTIONS[0] + SECTIONS[1] + SECTIONS[2];
+        endgame[castle..castle + 2].fill(0);
+        Self { midgame, endgame }
+    }
+}
+
+impl From<&[u8]> for TaperedEvaluator {
+    fn from(value: &[u8]) -> Self {
+        Self {
+            midgame: value[..evaluator_0::BYTES].try_into().unwrap(),
+            endgame: value[evaluator_0::BYTES..BYTES].try_into().unwrap(),
+        }
+    }
+}
+
+impl FromStr for TaperedEvaluator {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != BYTES {
+            return Err("string representation of a tapered evaluator has the wrong length");
+        }
+        Ok(s.chars()
+            .map(|x| match x {
+                '!'..='~' => Ok(x as u8 - 33),
+                _ => Err("should be valid ascii"),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .as_slice()
+            .into())
+    }
+}
+
+impl From<TaperedEvaluator> for String {
+    fn from(value: TaperedEvaluator) -> Self {
+        value
+            .midgame
+            .iter()
+            .chain(&value.endgame)
+            .map(|b| (b + 33) as char)
+            .collect()
+    }
+}
+
+/// Game phase from the pieces left on the board, between 0 and `MAX_PHASE`
+pub fn phase(board: &Board) -> isize {
+    let phase = PHASE_WEIGHTS
+        .iter()
+        .zip(board.piece_bitboards)
+        .map(|(weight, pieces)| weight * pieces.count_ones() as isize)
+        .sum::<isize>();
+    // Promotions can give more pieces than at the start
+    phase.min(MAX_PHASE)
+}
+
+impl Eval for TaperedEvaluator {
+    /// Both evaluations use the same features, so the board is only looked
+    /// at once
+    fn evaluate(&self, board: &mut Board) -> isize {
+        let features = evaluator_0::features(board);
+        let (mut midgame, mut endgame) = (0, 0);
+        for ((feature, m), e) in features.iter().zip(&self.midgame).zip(&self.endgame) {
+            midgame += feature * *m as isize;
+            endgame += feature * *e as isize;
+        }
+        let phase = phase(board);
+        (midgame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+
+    fn modified(&self, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        let mut child = self.clone();
+        for b in child.midgame.iter_mut().chain(child.endgame.iter_mut()) {
+            if rng.gen_bool(1.0 / 5.0) {
+                *b = (*b as isize + rng.gen_range(-5..=5)).clamp(0, 93) as u8;
+            }
+        }
+        Box::new(child)
+    }
+
+    /// Every field of every phase comes from one of the parents as a whole
+    fn crossover(&self, other: &dyn Eval, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+        let Ok(other) = TaperedEvaluator::from_str(&other.string_rep()) else {
+            return Box::new(self.clone());
+        };
+        let mut child = self.clone();
+        for (values, other_values) in [
+            (&mut child.midgame, &other.midgame),
+            (&mut child.endgame, &other.endgame),
+        ] {
+            for field in evaluator_0::fields() {
+                if rng.gen_bool(0.5) {
+                    values[field.clone()].copy_from_slice(&other_values[field]);
+                }
+            }
+        }
+        Box::new(child)
+    }
+
+    fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
+        Box::new(self.clone())
+    }
+
+    fn string_rep(&self) -> String {
+        String::from(self.clone())
+    }
+}
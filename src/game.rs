@@ -1,38 +1,508 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng};
+
 use crate::{
     board::{self, GameState, Player},
+    tree_evaluator::{SearchHandle, SearchLimits, SearchResult},
     User,
 };
 
-#[cfg(not(feature = "gui"))]
-pub fn run(white_player: &mut User, black_player: &mut User) -> GameState {
-    let mut board = board::Board::default();
+/// Play a game between two bots. Both bots are reseeded from `rng` first,
+/// so the same seed replays the same game.
+pub fn run(white_player: &mut User, black_player: &mut User, rng: &mut StdRng) -> GameState {
+    run_from(board::Board::default(), white_player, black_player, rng)
+}
+
+/// Play a game between two bots starting from `board`
+pub fn run_from(
+    board: board::Board,
+    white_player: &mut User,
+    black_player: &mut User,
+    rng: &mut StdRng,
+) -> GameState {
+    run_recorded(board, white_player, black_player, rng, |_, _| ())
+}
+
+/// Like `run_from`, but `record` is called with the board and the bot's
+/// search result before every move
+pub fn run_recorded<F>(
+    board: board::Board,
+    white_player: &mut User,
+    black_player: &mut User,
+    rng: &mut StdRng,
+    record: F,
+) -> GameState
+where
+    F: FnMut(&board::Board, &SearchResult),
+{
+    run_with(
+        board,
+        white_player,
+        black_player,
+        &Adjudication::default(),
+        None,
+        rng,
+        record,
+    )
+    .state
+}
+
+/// A side resigns when the scores of both bots are beyond `score` in its
+/// opponent's favor for `moves` moves in a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resign {
+    pub score: isize,
+    pub moves: usize,
+}
+
+/// The game is drawn when the scores of both bots are within `score` of 0 for
+/// `moves` moves in a row, counting from move `after` on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LowScoreDraw {
+    pub score: isize,
+    pub moves: usize,
+    pub after: usize,
+}
+
+/// Rules for ending bot games early. Scores are in the bots' own evaluation
+/// units, moves count from the position the game started in and a move is
+/// one by each side. Nothing is adjudicated by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Adjudication {
+    pub resign: Option<Resign>,
+    pub draw: Option<LowScoreDraw>,
+    /// The game is drawn after this many moves
+    pub max_moves: Option<usize>,
+}
+
+/// Clock settings of a game, the same for both sides. With `moves_to_go`
+/// the base time is added again after every `moves_to_go` moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+    pub moves_to_go: Option<u32>,
+}
+
+/// Why a game ended before it was over on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Adjudicated {
+    Resignation,
+    LowScore,
+    MaxMoves,
+    /// A bot used more time than it had on its clock
+    Time,
+}
+
+impl Adjudicated {
+    pub const ALL: [Adjudicated; 4] = [
+        Adjudicated::Resignation,
+        Adjudicated::LowScore,
+        Adjudicated::MaxMoves,
+        Adjudicated::Time,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    /// Never `GameState::Playing`
+    pub state: GameState,
+    /// None if the game ended by the rules
+    pub adjudicated: Option<Adjudicated>,
+}
+
+/// Parse comma separated numbers, exactly `N` of them
+fn numbers<const N: usize>(s: &str) -> Result<[isize; N], String> {
+    let numbers = s
+        .split(',')
+        .map(|n| n.trim().parse::<isize>().map_err(|e| format!("{n}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    numbers
+        .try_into()
+        .map_err(|_| format!("expected {N} comma separated numbers"))
+}
+
+impl FromStr for Resign {
+    type Err = String;
+    /// `SCORE,MOVES`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [score, moves] = numbers(s)?;
+        if score <= 0 || moves <= 0 {
+            return Err(String::from("score and moves must be positive"));
+        }
+        Ok(Resign {
+            score,
+            moves: moves as usize,
+        })
+    }
+}
+
+impl Display for Resign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.score, self.moves)
+    }
+}
+
+impl FromStr for LowScoreDraw {
+    type Err = String;
+    /// `SCORE,MOVES,AFTER`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [score, moves, after] = numbers(s)?;
+        if score < 0 || moves <= 0 || after < 0 {
+            return Err(String::from(
+                "moves must be positive, score and after can't be negative",
+            ));
+        }
+        Ok(LowScoreDraw {
+            score,
+            moves: moves as usize,
+            after: after as usize,
+        })
+    }
+}
+
+impl Display for LowScoreDraw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.score, self.moves, self.after)
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+    /// `[MOVES/]BASE[+INCREMENT]` in seconds, e.g. `10+0.1` or `40/60`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (moves_to_go, time) = match s.split_once('/') {
+            Some((moves, time)) => (
+                Some(
+                    moves
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|m| *m > 0)
+                        .ok_or(format!("invalid number of moves {moves}"))?,
+                ),
+                time,
+            ),
+            None => (None, s),
+        };
+        let (base, increment) = time.split_once('+').unwrap_or((time, "0"));
+        let seconds = |t: &str| {
+            t.parse::<f64>()
+                .ok()
+                .and_then(|t| Duration::try_from_secs_f64(t).ok())
+                .ok_or(format!("invalid time {t}"))
+        };
+        let base = seconds(base)?;
+        if base.is_zero() {
+            return Err(String::from("the base time must be positive"));
+        }
+        Ok(TimeControl {
+            base,
+            increment: seconds(increment)?,
+            moves_to_go,
+        })
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(moves) = self.moves_to_go {
+            write!(f, "{moves}/")?;
+        }
+        write!(f, "{}", self.base.as_secs_f64())?;
+        if !self.increment.is_zero() {
+            write!(f, "+{}", self.increment.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Adjudicated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Adjudicated::Resignation => write!(f, "resignation"),
+            Adjudicated::LowScore => write!(f, "low score"),
+            Adjudicated::MaxMoves => write!(f, "maximum length"),
+            Adjudicated::Time => write!(f, "time forfeit"),
+        }
+    }
+}
+
+/// Like `run_recorded`, but the game ends early when `adjudication` says so.
+/// With a time control the bots search until their share of the time on
+/// their clock is used up instead of to their depth, and lose when they
+/// take longer than the time they have left.
+pub fn run_with<F>(
+    mut board: board::Board,
+    white_player: &mut User,
+    black_player: &mut User,
+    adjudication: &Adjudication,
+    time_control: Option<&TimeControl>,
+    rng: &mut StdRng,
+    mut record: F,
+) -> Outcome
+where
+    F: FnMut(&board::Board, &SearchResult),
+{
+    for player in [&mut *white_player, &mut *black_player] {
+        if let User::Bot(b) = player {
+            b.new_game(rng.gen());
+        }
+    }
+    // Indexed by `Player::idx`
+    let mut clocks = [time_control.map(|tc| tc.base); 2];
+    let mut plies = 0;
+    // Plies in a row with a score beyond the resign score, positive when
+    // white is ahead
+    let mut resign_plies = 0isize;
+    // Plies in a row with a score close to 0
+    let mut draw_plies = 0;
     loop {
-        match board.turn {
-            Player::White => match white_player {
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut board);
-                    if let Some(m) = cmove {
-                        board.make_move(&m);
-                    }
-                }
-                _ => {
-                    panic!("should not have humans")
-                }
-            },
-            Player::Black => match black_player {
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut board);
-                    if let Some(m) = cmove {
-                        board.make_move(&m);
-                    }
-                }
-                _ => panic!("should not have humans"),
+        let player = match board.turn {
+            Player::White => &mut *white_player,
+            Player::Black => &mut *black_player,
+        };
+        let User::Bot(b) = player else {
+            panic!("should not have humans")
+        };
+        let side = board.turn.idx();
+        let limits = match (time_control, clocks[side]) {
+            (Some(tc), Some(time)) => SearchLimits {
+                time: Some(time),
+                increment: tc.increment,
+                moves_to_go: tc.moves_to_go.map(|m| m - (plies / 2) as u32 % m),
+                ..Default::default()
             },
+            _ => SearchLimits::default(),
+        };
+        let start = Instant::now();
+        let result = b.search_within(&mut board, &limits, &SearchHandle::default());
+        if let (Some(tc), Some(time)) = (time_control, &mut clocks[side]) {
+            let Some(left) = time.checked_sub(start.elapsed()) else {
+                let winner = match board.turn {
+                    Player::White => Player::Black,
+                    Player::Black => Player::White,
+                };
+                return Outcome {
+                    state: GameState::Win(winner),
+                    adjudicated: Some(Adjudicated::Time),
+                };
+            };
+            *time = left + tc.increment;
+            if tc
+                .moves_to_go
+                .is_some_and(|m| (plies / 2 + 1) % m as usize == 0)
+            {
+                *time += tc.base;
+            }
+        }
+        record(&board, &result);
+        let score = match board.turn {
+            Player::White => result.score,
+            Player::Black => -result.score,
+        };
+        if let Some(m) = &result.best_move {
+            // A bot that plays an illegal move loses, the board is unchanged then
+            if board.make_move(m).is_err() {
+                let winner = match board.turn {
+                    Player::White => Player::Black,
+                    Player::Black => Player::White,
+                };
+                return Outcome {
+                    state: GameState::Win(winner),
+                    adjudicated: None,
+                };
+            }
         }
+        plies += 1;
 
         match board.get_game_state() {
             GameState::Playing => (),
-            _ => return board.get_game_state(),
+            state => {
+                return Outcome {
+                    state,
+                    adjudicated: None,
+                }
+            }
+        }
+
+        // Book moves come without a score
+        let searched = result.depth > 0;
+        if let Some(resign) = adjudication.resign {
+            resign_plies = match score {
+                _ if !searched => 0,
+                s if s >= resign.score => resign_plies.max(0) + 1,
+                s if s <= -resign.score => resign_plies.min(0) - 1,
+                _ => 0,
+            };
+            if resign_plies.unsigned_abs() >= 2 * resign.moves {
+                let winner = if resign_plies > 0 {
+                    Player::White
+                } else {
+                    Player::Black
+                };
+                return Outcome {
+                    state: GameState::Win(winner),
+                    adjudicated: Some(Adjudicated::Resignation),
+                };
+            }
+        }
+        if let Some(draw) = adjudication.draw {
+            if searched && plies > 2 * draw.after && score.abs() <= draw.score {
+                draw_plies += 1;
+            } else {
+                draw_plies = 0;
+            }
+            if draw_plies >= 2 * draw.moves {
+                return Outcome {
+                    state: GameState::Draw,
+                    adjudicated: Some(Adjudicated::LowScore),
+                };
+            }
         }
+        if adjudication.max_moves.is_some_and(|m| plies >= 2 * m) {
+            return Outcome {
+                state: GameState::Draw,
+                adjudicated: Some(Adjudicated::MaxMoves),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tree_evaluator::{Bot, Eval};
+
+    /// Gives every position the same score, from white's perspective, and
+    /// takes `delay` for it
+    #[derive(Clone)]
+    struct Scripted {
+        score: isize,
+        delay: Duration,
+    }
+
+    impl Eval for Scripted {
+        fn evaluate(&self, _board: &mut board::Board) -> isize {
+            std::thread::sleep(self.delay);
+            self.score
+        }
+        fn modified(&self, _rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+            Box::new(self.clone())
+        }
+        fn crossover(&self, _other: &dyn Eval, _rng: &mut StdRng) -> Box<dyn Eval + Sync + Send> {
+            Box::new(self.clone())
+        }
+        fn bot_clone(&self) -> Box<dyn Eval + Sync + Send> {
+            Box::new(self.clone())
+        }
+        fn string_rep(&self) -> String {
+            String::new()
+        }
+    }
+
+    fn scripted(score: isize, delay: Duration) -> User {
+        User::Bot(Box::new(Bot::new(Box::new(Scripted { score, delay }), 1)))
+    }
+
+    /// Play from the start position, returning the outcome and the number of
+    /// moves that were made
+    fn play(
+        mut white: User,
+        mut black: User,
+        adjudication: &Adjudication,
+        time_control: Option<&TimeControl>,
+    ) -> (Outcome, usize) {
+        let mut plies = 0;
+        let outcome = run_with(
+            board::Board::default(),
+            &mut white,
+            &mut black,
+            adjudication,
+            time_control,
+            &mut StdRng::seed_from_u64(0),
+            |_, _| plies += 1,
+        );
+        (outcome, plies)
+    }
+
+    #[test]
+    fn resigns_after_window() {
+        let adjudication = Adjudication {
+            resign: Some(Resign {
+                score: 300,
+                moves: 3,
+            }),
+            ..Default::default()
+        };
+        let white_ahead = || scripted(500, Duration::ZERO);
+        let (outcome, plies) = play(white_ahead(), white_ahead(), &adjudication, None);
+        assert_eq!(outcome.state, GameState::Win(Player::White));
+        assert_eq!(outcome.adjudicated, Some(Adjudicated::Resignation));
+        assert_eq!(plies, 6);
+    }
+
+    #[test]
+    fn low_score_draw_after_window() {
+        let adjudication = Adjudication {
+            draw: Some(LowScoreDraw {
+                score: 10,
+                moves: 3,
+                after: 5,
+            }),
+            ..Default::default()
+        };
+        let even = || scripted(0, Duration::ZERO);
+        let (outcome, plies) = play(even(), even(), &adjudication, None);
+        assert_eq!(outcome.state, GameState::Draw);
+        assert_eq!(outcome.adjudicated, Some(Adjudicated::LowScore));
+        // Counting starts after move 5, then 3 moves of both sides
+        assert_eq!(plies, 16);
+    }
+
+    #[test]
+    fn max_moves() {
+        let adjudication = Adjudication {
+            max_moves: Some(5),
+            ..Default::default()
+        };
+        let even = || scripted(0, Duration::ZERO);
+        let (outcome, plies) = play(even(), even(), &adjudication, None);
+        assert_eq!(outcome.state, GameState::Draw);
+        assert_eq!(outcome.adjudicated, Some(Adjudicated::MaxMoves));
+        assert_eq!(plies, 10);
+    }
+
+    #[test]
+    fn slow_bot_loses_on_time() {
+        let time_control: TimeControl = "0.5+0.1".parse().unwrap();
+        let fast = scripted(0, Duration::ZERO);
+        // Even the first depth takes longer than the whole clock
+        let slow = scripted(0, Duration::from_millis(50));
+        let (outcome, plies) = play(fast, slow, &Adjudication::default(), Some(&time_control));
+        assert_eq!(outcome.state, GameState::Win(Player::White));
+        assert_eq!(outcome.adjudicated, Some(Adjudicated::Time));
+        assert_eq!(plies, 1);
+    }
+
+    /// Every move takes about half the increment, more than the base time
+    /// in total, so the game only lasts if the increment is added
+    #[test]
+    fn increment_keeps_clock_running() {
+        let time_control: TimeControl = "0.2+0.2".parse().unwrap();
+        let adjudication = Adjudication {
+            max_moves: Some(4),
+            ..Default::default()
+        };
+        let even = || scripted(0, Duration::ZERO);
+        let (outcome, plies) = play(even(), even(), &adjudication, Some(&time_control));
+        assert_eq!(outcome.adjudicated, Some(Adjudicated::MaxMoves));
+        assert_eq!(plies, 8);
     }
 }
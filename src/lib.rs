@@ -1,13 +1,20 @@
 pub mod board;
+pub mod book;
+pub mod cli;
 pub mod cmove;
 pub mod compile;
 pub mod evaluators;
 pub mod game;
+pub mod openings;
 pub mod parse;
 pub mod piece;
+pub mod pool;
+pub mod stats;
+pub mod transposition;
 pub mod tree_evaluator;
+pub mod zobrist;
 #[derive(Clone)]
 pub enum User {
     Human,
-    Bot(tree_evaluator::Bot),
+    Bot(Box<tree_evaluator::Bot>),
 }
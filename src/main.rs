@@ -4,11 +4,13 @@ use chlang::cmove::Move;
 use chlang::evaluators::evaluator_0;
 use chlang::parse::parse;
 use chlang::piece::Piece;
-use chlang::tree_evaluator::Bot;
+use chlang::tree_evaluator::{Bot, SearchHandle, SearchResult};
 use rustc_hash::FxHashMap;
 use std::fs::{self, write, File};
 use std::io::{stdin, BufRead, Read, Write};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 
 use chlang::board;
 use chlang::parse;
@@ -17,7 +19,7 @@ use chlang::User;
 
 use pix_engine::prelude::{
     circle, point, rect, square, BlendMode, Color, Engine as PEngine, Flipped, Image, ImageMode,
-    Mouse, PixEngine, PixError, PixResult, PixState, Point, RectMode,
+    Key, KeyEvent, Mouse, PixEngine, PixError, PixResult, PixState, Point, RectMode,
 };
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -61,9 +63,24 @@ pub struct Game {
     black_player: User,
     white_player: User,
     promotion: Option<Position>,
+    search: Option<(SearchHandle, JoinHandle<Option<Move>>)>,
+    progress: Arc<Mutex<Option<SearchResult>>>,
 }
 
 impl PixEngine for Game {
+    fn on_key_pressed(&mut self, _s: &mut PixState, event: KeyEvent) -> PixResult<bool> {
+        match event.key {
+            // Make the bot play the best move it has found so far
+            Key::Space | Key::Escape => {
+                if let Some((ref handle, _)) = self.search {
+                    handle.stop();
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
     fn on_update(&mut self, s: &mut PixState) -> PixResult<()> {
         s.image_mode(ImageMode::Center);
         s.font_size(25)?;
@@ -76,33 +93,13 @@ impl PixEngine for Game {
         }
 
         // If current player is bot then let it play
-        match self.board.turn {
-            Player::White => match self.white_player {
-                User::Human => (),
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut self.board);
-                    if let Some(cmove) = cmove {
-                        self.board
-                            .make_move(&cmove)
-                            .expect("bot made unvalid move?!");
-                    }
-                    self.state = self.board.get_game_state();
-                    return self.draw(s);
-                }
-            },
-            Player::Black => match self.black_player {
-                User::Human => (),
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut self.board);
-                    if let Some(cmove) = cmove {
-                        self.board
-                            .make_move(&cmove)
-                            .expect("bot made unvalid move?!");
-                    }
-                    self.state = self.board.get_game_state();
-                    return self.draw(s);
-                }
-            },
+        let bot_to_move = match self.board.turn {
+            Player::White => matches!(self.white_player, User::Bot(_)),
+            Player::Black => matches!(self.black_player, User::Bot(_)),
+        };
+        if bot_to_move {
+            self.bot_turn();
+            return self.draw(s);
         }
 
         if s.mouse_clicked(Mouse::Left) {
@@ -190,6 +187,40 @@ impl PixEngine for Game {
 }
 
 impl Game {
+    /// Start a search in the background, or play its move once it is done.
+    /// The search runs on its own thread so the window keeps updating.
+    fn bot_turn(&mut self) {
+        match self.search.take() {
+            None => {
+                let mut bot = match (&self.board.turn, &self.white_player, &self.black_player) {
+                    (Player::White, User::Bot(b), _) | (Player::Black, _, User::Bot(b)) => {
+                        b.clone()
+                    }
+                    _ => return,
+                };
+                let mut board = self.board.clone();
+                let progress = self.progress.clone();
+                let handle = SearchHandle::with_callback(move |result| {
+                    *progress.lock().unwrap() = Some(result.clone());
+                });
+                let search_handle = handle.clone();
+                let thread =
+                    thread::spawn(move || bot.search_with(&mut board, &search_handle).best_move);
+                self.search = Some((handle, thread));
+            }
+            Some((_, thread)) if thread.is_finished() => {
+                if let Some(cmove) = thread.join().expect("search thread panicked") {
+                    self.board
+                        .make_move(&cmove)
+                        .expect("bot made unvalid move?!");
+                }
+                *self.progress.lock().unwrap() = None;
+                self.state = self.board.get_game_state();
+            }
+            search => self.search = search,
+        }
+    }
+
     fn draw(&self, s: &mut PixState) -> PixResult<()> {
         // draw board
         for p in 0..64 {
@@ -236,6 +267,15 @@ impl Game {
             }
         }
 
+        if let Some(ref result) = *self.progress.lock().unwrap() {
+            s.fill(Color::WHITE);
+            s.set_cursor_pos(point!(10, 10));
+            s.text(format!(
+                "depth {} score {} (space to stop)",
+                result.depth, result.score
+            ))?;
+        }
+
         if let Some(ref p) = self.promotion {
             s.fill(Color::DARK_GRAY);
             s.rect(rect![s.center()?, 160, 40])?;
@@ -322,6 +362,8 @@ impl Default for Game {
             black_player: User::Human,
             white_player: User::Human,
             promotion: None,
+            search: None,
+            progress: Arc::new(Mutex::new(None)),
         }
     }
 }
@@ -0,0 +1,49 @@
+//! Start positions for bot matches, read from EPD or FEN files
+
+use std::{fs, io, path::Path};
+
+use crate::board::Board;
+
+#[derive(Clone)]
+pub struct Opening {
+    pub name: String,
+    pub board: Board,
+}
+
+impl Opening {
+    /// Parse one line of an EPD or FEN file. The name is taken from an `id`
+    /// operation if there is one, otherwise it is the position itself.
+    pub fn parse(line: &str) -> Result<Opening, &'static str> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 4 {
+            return Err("position needs at least 4 fields");
+        }
+        // FEN has two move counters after the en passant square, EPD has operations
+        let counters = fields[4..]
+            .iter()
+            .take(2)
+            .take_while(|f| f.parse::<u32>().is_ok())
+            .count();
+        let fen = fields[..4 + counters].join(" ");
+        let board = Board::from_fen(&fen)?;
+
+        let operations = fields[4 + counters..].join(" ");
+        let name = operations
+            .split(';')
+            .find_map(|op| op.trim().strip_prefix("id "))
+            .map(|id| id.trim().trim_matches('"').to_string())
+            .unwrap_or(fields[..4].join(" "));
+
+        Ok(Opening { name, board })
+    }
+}
+
+/// All positions of the file, empty lines and lines starting with `#` are skipped
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<Opening>> {
+    fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| Opening::parse(l).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+        .collect()
+}
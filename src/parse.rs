@@ -1,8 +1,11 @@
-use std::{env::Args, error::Error, str::FromStr};
+use std::{error::Error, str::FromStr, sync::Arc};
 
-use rustc_hash::FxHashMap;
-
-use crate::{evaluators, tree_evaluator, User};
+use crate::{
+    book::OpeningBook,
+    evaluators,
+    tree_evaluator::{self, SearchOptions},
+    User,
+};
 
 pub fn parse<T>(a: &mut T) -> Result<User, Box<dyn Error>>
 where
@@ -11,60 +14,151 @@ where
     if let Some(s) = a.next() {
         match s.as_str() {
             "HUMAN" => Ok(User::Human),
-            "MATERIAL" => Ok(User::Bot(tree_evaluator::Bot {
-                evaluator: Box::new(
+            "MATERIAL" => {
+                let evaluator = Box::new(
                     #[cfg(feature = "using_default")]
                     evaluators::material_evaluator::MaterialEvaluator::default(),
                     #[cfg(not(feature = "using_default"))]
                     evaluators::material_evaluator::MaterialEvaluator::from_str(
-                        &a.next().expect("insert string representation"),
+                        &a.next()
+                            .ok_or("Please insert string representation for MATERIAL bot")?,
                     )?,
-                ),
-                search_depth: a
+                );
+                let search_depth = search_depth(a, "MATERIAL")?;
+                Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                    evaluator,
+                    search_depth,
+                ))))
+            }
+            "POSITIONAL" => {
+                let search_depth = search_depth(a, "POSITIONAL")?;
+                Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                    Box::new(
+                        #[cfg(feature = "using_default")]
+                        evaluators::positional_evaluator::PositionalEvaluator::default(),
+                        #[cfg(not(feature = "using_default"))]
+                        evaluators::positional_evaluator::PositionalEvaluator::from_str(
+                            &a.next()
+                                .ok_or("Please insert string representation for POSITIONAL bot")?,
+                        )?,
+                    ),
+                    search_depth,
+                ))))
+            }
+            // TAPERED <rep> <depth>, DEFAULT as rep for the default values
+            "TAPERED" => {
+                let rep = a
                     .next()
-                    .expect("Please insert search depth for MATERIAL bot")
-                    .parse::<u8>()
-                    .expect("Invalid search depth: must be a valid u8"),
-                cache: FxHashMap::default(),
-            })),
-            "POSITIONAL" => Ok(User::Bot(tree_evaluator::Bot {
-                search_depth: a
+                    .ok_or("Please insert string representation for TAPERED bot")?;
+                let evaluator = match rep.as_str() {
+                    "DEFAULT" => evaluators::tapered_evaluator::TaperedEvaluator::default(),
+                    rep => evaluators::tapered_evaluator::TaperedEvaluator::from_str(rep)?,
+                };
+                let search_depth = search_depth(a, "TAPERED")?;
+                Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                    Box::new(evaluator),
+                    search_depth,
+                ))))
+            }
+            // PAWNS <rep> <depth>, evaluator_1 with pawn structure values
+            "PAWNS" => {
+                let rep = a
                     .next()
-                    .expect("Please insert search depth for MATERIAL bot")
-                    .parse::<u8>()
-                    .expect("Invalid search depth: must be a valid u8"),
-                evaluator: Box::new(
-                    #[cfg(feature = "using_default")]
-                    evaluators::positional_evaluator::PositionalEvaluator::default(),
-                    #[cfg(not(feature = "using_default"))]
-                    evaluators::positional_evaluator::PositionalEvaluator::from_str(
-                        &a.next()
-                            .expect("insert string representation of eval function"),
-                    )?,
-                ),
-                cache: FxHashMap::default(),
-            })),
-            "RANDOM" => Ok(User::Bot(tree_evaluator::Bot {
-                evaluator: Box::new(evaluators::NoneEvaluator),
-                search_depth: 1,
-                cache: FxHashMap::default(),
-            })),
+                    .ok_or("Please insert string representation for PAWNS bot")?;
+                let evaluator = match rep.as_str() {
+                    "DEFAULT" => evaluators::evaluator_1::Evaluator::default(),
+                    rep => evaluators::evaluator_1::Evaluator::from_str(rep)?,
+                };
+                let search_depth = search_depth(a, "PAWNS")?;
+                Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                    Box::new(evaluator),
+                    search_depth,
+                ))))
+            }
+            // KINGSAFETY <rep> <depth>, evaluator_2 with pawn structure and king safety values
+            "KINGSAFETY" => {
+                let rep = a
+                    .next()
+                    .ok_or("Please insert string representation for KINGSAFETY bot")?;
+                let evaluator = match rep.as_str() {
+                    "DEFAULT" => evaluators::evaluator_2::Evaluator::default(),
+                    rep => evaluators::evaluator_2::Evaluator::from_str(rep)?,
+                };
+                let search_depth = search_depth(a, "KINGSAFETY")?;
+                Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                    Box::new(evaluator),
+                    search_depth,
+                ))))
+            }
+            "RANDOM" => Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                Box::new(evaluators::NoneEvaluator),
+                1,
+            )))),
 
-            "DEFAULT" => Ok(User::Bot(tree_evaluator::Bot {
-                evaluator: Box::new(evaluators::evaluator_0::Evaluator::default()),
-                search_depth: 4,
-                cache: FxHashMap::default(),
-            })),
+            "DEFAULT" => Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                Box::new(evaluators::evaluator_0::Evaluator::default()),
+                4,
+            )))),
+
+            // SEARCH <options> <bot>, e.g. `SEARCH nmp,lmr DEFAULT`
+            "SEARCH" => {
+                let options = a
+                    .next()
+                    .ok_or("Please insert search options (nmp,lmr,ext, all or none)")?
+                    .parse::<SearchOptions>()?;
+                match parse(a)? {
+                    User::Bot(mut b) => {
+                        b.options = options;
+                        Ok(User::Bot(b))
+                    }
+                    User::Human => Err("Search options can only be set for bots")?,
+                }
+            }
 
-            s => Ok(User::Bot(tree_evaluator::Bot {
-                evaluator: Box::new(
-                    evaluators::evaluator_0::Evaluator::from_str(s).expect("Bad string rep"),
-                ),
-                search_depth: 4,
-                cache: FxHashMap::default(),
-            })),
+            // THREADS <n> <bot>, e.g. `THREADS 8 DEFAULT`
+            "THREADS" => {
+                let threads = a
+                    .next()
+                    .ok_or("Please insert number of search threads")?
+                    .parse::<usize>()?;
+                match parse(a)? {
+                    User::Bot(mut b) => {
+                        b.threads = threads.max(1);
+                        Ok(User::Bot(b))
+                    }
+                    User::Human => Err("Search threads can only be set for bots")?,
+                }
+            }
+
+            // BOOK <path> <bot>, e.g. `BOOK openings.bin DEFAULT`
+            "BOOK" => {
+                let path = a.next().ok_or("Please insert path to a polyglot book")?;
+                let book = OpeningBook::open(&path)?;
+                match parse(a)? {
+                    User::Bot(mut b) => {
+                        b.book = Some(Arc::new(book));
+                        Ok(User::Bot(b))
+                    }
+                    User::Human => Err("Opening books can only be used by bots")?,
+                }
+            }
+
+            s => Ok(User::Bot(Box::new(tree_evaluator::Bot::new(
+                Box::new(evaluators::evaluator_0::Evaluator::from_str(s)?),
+                4,
+            )))),
         }
     } else {
         Ok(User::Human)
     }
 }
+
+fn search_depth<T>(a: &mut T, bot: &str) -> Result<u8, Box<dyn Error>>
+where
+    T: Iterator<Item = String>,
+{
+    a.next()
+        .ok_or(format!("Please insert search depth for {bot} bot"))?
+        .parse::<u8>()
+        .map_err(|_| "Invalid search depth: must be a valid u8".into())
+}
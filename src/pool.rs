@@ -0,0 +1,51 @@
+//! Running independent jobs, like bot games, on several threads
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+};
+
+/// Run `work` on every job using `concurrency` threads. `done` gets every
+/// result on the calling thread as soon as it is finished, so results come in
+/// the order the jobs finish. When `done` returns false no new jobs are started,
+/// the results of jobs that are still running are passed to `done` anyway.
+pub fn run<J, R, W, D>(jobs: Vec<J>, concurrency: usize, work: W, mut done: D)
+where
+    J: Send,
+    R: Send,
+    W: Fn(J) -> R + Sync,
+    D: FnMut(R) -> bool,
+{
+    let queue = Mutex::new(VecDeque::from(jobs));
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..concurrency.max(1) {
+            let sender = sender.clone();
+            let (queue, stop, work) = (&queue, &stop, &work);
+            s.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let Some(job) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    if sender.send(work(job)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // The receiver ends once every worker dropped its sender
+        drop(sender);
+
+        for result in receiver {
+            if !done(result) {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+}
@@ -0,0 +1,247 @@
+//! Statistics for bot matches: Elo difference, likelihood of superiority
+//! and the sequential probability ratio test
+
+use std::{fmt::Display, str::FromStr};
+
+/// Results of a match from the perspective of the first bot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Average points per game, a draw is half a point
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Variance of the points of a single game
+    fn variance(&self) -> f64 {
+        let n = self.games() as f64;
+        let score = self.score();
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / n
+    }
+
+    /// Elo difference and the margin of its 95% confidence interval. The margin
+    /// is infinite while the interval still reaches a score of 0 or 1, and
+    /// while every game had the same result so there is no variance to go by.
+    /// None when there is no game or no game was won or lost by both bots.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let (low, high) = (score - 1.959964 * deviation, score + 1.959964 * deviation);
+        let margin = if deviation == 0.0 || low <= 0.0 || high >= 1.0 {
+            f64::INFINITY
+        } else {
+            (elo_from_score(high) - elo_from_score(low)) / 2.0
+        };
+        Some((elo_from_score(score), margin))
+    }
+
+    /// Likelihood of superiority: the probability that the first bot is the
+    /// stronger one. Draws don't tell anything about this.
+    pub fn los(&self) -> f64 {
+        if self.wins + self.losses == 0 {
+            return 0.5;
+        }
+        let diff = self.wins as f64 - self.losses as f64;
+        0.5 * (1.0 + erf(diff / (2.0 * (self.wins + self.losses) as f64).sqrt()))
+    }
+}
+
+impl Display for MatchScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.elo() {
+            Some((elo, margin)) => write!(f, "Elo: {elo:.1} +/- {margin:.1}")?,
+            None => write!(f, "Elo: -")?,
+        }
+        write!(f, "\nLOS: {:.1}%", self.los() * 100.0)
+    }
+}
+
+pub fn elo_from_score(score: f64) -> f64 {
+    // Adding zero turns -0 into 0 for even scores
+    -400.0 * (1.0 / score - 1.0).log10() + 0.0
+}
+
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Error function, accurate to about 1e-7 (Abramowitz and Stegun 7.1.26)
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let y = 1.0 - polynomial * (-x * x).exp();
+    y.copysign(x)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtState {
+    Continue,
+    /// The difference is at most `elo0`
+    AcceptH0,
+    /// The difference is at least `elo1`
+    AcceptH1,
+}
+
+/// Test of `elo <= elo0` against `elo >= elo1` with error rates `alpha` and `beta`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn lower_bound(&self) -> f64 {
+        (self.beta / (1.0 - self.alpha)).ln()
+    }
+
+    pub fn upper_bound(&self) -> f64 {
+        ((1.0 - self.beta) / self.alpha).ln()
+    }
+
+    /// Log likelihood ratio, using the normal approximation of the
+    /// generalized SPRT on the score of each game. The variance is taken as
+    /// if one more game had been won and one more lost, so that it is never
+    /// 0 and a match where every game has the same result still ends.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let variance = MatchScore {
+            wins: score.wins + 1,
+            losses: score.losses + 1,
+            draws: score.draws,
+        }
+        .variance();
+        let s0 = score_from_elo(self.elo0);
+        let s1 = score_from_elo(self.elo1);
+        score.games() as f64 * (s1 - s0) * (2.0 * score.score() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn state(&self, score: &MatchScore) -> SprtState {
+        let llr = self.llr(score);
+        if llr >= self.upper_bound() {
+            SprtState::AcceptH1
+        } else if llr <= self.lower_bound() {
+            SprtState::AcceptH0
+        } else {
+            SprtState::Continue
+        }
+    }
+}
+
+/// `elo0,elo1,alpha,beta`, e.g. `0,5,0.05,0.05`
+impl FromStr for Sprt {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "sprt parameters must be numbers")?;
+        let [elo0, elo1, alpha, beta] = values[..] else {
+            return Err("sprt needs elo0,elo1,alpha,beta");
+        };
+        if elo0 >= elo1 {
+            return Err("sprt elo0 must be lower than elo1");
+        }
+        if alpha <= 0.0 || alpha >= 1.0 || beta <= 0.0 || beta >= 1.0 {
+            return Err("sprt alpha and beta must be between 0 and 1");
+        }
+        Ok(Sprt {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        })
+    }
+}
+
+impl Display for Sprt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SPRT elo0={} elo1={} alpha={} beta={}",
+            self.elo0, self.elo1, self.alpha, self.beta
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprt_stops_one_sided_match() {
+        let sprt: Sprt = "0,10,0.05,0.05".parse().unwrap();
+        let score = MatchScore {
+            wins: 30,
+            losses: 0,
+            draws: 10,
+        };
+        assert!(sprt.llr(&score) > sprt.upper_bound());
+        assert_eq!(sprt.state(&score), SprtState::AcceptH1);
+        let score = MatchScore {
+            wins: 0,
+            losses: 30,
+            draws: 10,
+        };
+        assert_eq!(sprt.state(&score), SprtState::AcceptH0);
+    }
+
+    #[test]
+    fn sprt_stops_sweep() {
+        let sprt: Sprt = "0,10,0.05,0.05".parse().unwrap();
+        let mut score = MatchScore::default();
+        while sprt.state(&score) == SprtState::Continue {
+            score.wins += 1;
+            assert!(score.wins <= 100, "sprt never stops a sweep");
+        }
+        assert_eq!(sprt.state(&score), SprtState::AcceptH1);
+
+        let mut score = MatchScore::default();
+        while sprt.state(&score) == SprtState::Continue {
+            score.losses += 1;
+            assert!(score.losses <= 100, "sprt never stops a sweep");
+        }
+        assert_eq!(sprt.state(&score), SprtState::AcceptH0);
+    }
+
+    #[test]
+    fn elo_margin_without_variance() {
+        let draws = MatchScore {
+            wins: 0,
+            losses: 0,
+            draws: 2,
+        };
+        assert_eq!(draws.elo(), Some((0.0, f64::INFINITY)));
+        assert_eq!(draws.to_string(), "Elo: 0.0 +/- inf\nLOS: 50.0%");
+
+        let even = MatchScore {
+            wins: 10,
+            losses: 10,
+            draws: 0,
+        };
+        let (elo, margin) = even.elo().unwrap();
+        assert_eq!(elo, 0.0);
+        assert!(margin.is_finite() && margin > 0.0);
+    }
+}
@@ -0,0 +1,121 @@
+use chlang::board::{self, Player};
+use chlang::cmove::Move;
+use chlang::evaluators::evaluator_0;
+use chlang::parse;
+use chlang::tree_evaluator::{Bot, SearchLimits};
+use chlang::User;
+use std::io::BufRead;
+
+/// Read one command of a human player: a move, `u` to undo the last move or
+/// `a [N]` to print the N best moves. Returns false if the board should not
+/// be redrawn, also when the move is illegal.
+fn human_turn(board: &mut board::Board, analyser: &mut Bot, stdin: &mut impl BufRead) -> bool {
+    let mut input = String::new();
+    let _ = stdin.read_line(&mut input);
+    let mut words = input.split_whitespace();
+    match words.next() {
+        Some("u") => board.unmake_last(),
+        Some("a") => {
+            let lines = words.next().and_then(|n| n.parse().ok()).unwrap_or(3);
+            let limits = SearchLimits::depth(analyser.search_depth);
+            for result in analyser.analyse(board, &limits, lines) {
+                let moves = result
+                    .pv
+                    .iter()
+                    .map(|m| m.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let score = match result.mate_in() {
+                    Some(n) => format!("mate {n}"),
+                    None => result.score.to_string(),
+                };
+                println!(
+                    "{}. {score} (depth {}): {moves}",
+                    result.multipv, result.depth
+                );
+            }
+            return false;
+        }
+        _ => {
+            let cmove: Result<Move, &str> = input.parse();
+            if let Err(e) = cmove.and_then(|m| board.make_move(&m)) {
+                println!("{e}");
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut a = std::env::args();
+
+    // skip name of program
+    a.next();
+
+    let mut white_player = parse::parse(&mut a)?;
+
+    let mut black_player = parse::parse(&mut a)?;
+
+    let mut board = board::Board::default();
+    let mut analyser = Bot::new(Box::new(evaluator_0::Evaluator::default()), 4);
+
+    let mut stdin = std::io::BufReader::new(std::io::stdin());
+    println!("\x1b[2J\x1b[H");
+    println!("{}", board);
+
+    loop {
+        match board.turn {
+            Player::White => match white_player {
+                User::Human => {
+                    if !human_turn(&mut board, &mut analyser, &mut stdin) {
+                        continue;
+                    }
+                }
+                User::Bot(ref mut b) => {
+                    let cmove = b.find_best_move(&mut board);
+                    if let Some(m) = cmove {
+                        board
+                            .make_move(&m)
+                            .expect("bot should only play legal moves");
+                    }
+                }
+            },
+            Player::Black => match black_player {
+                User::Human => {
+                    if !human_turn(&mut board, &mut analyser, &mut stdin) {
+                        continue;
+                    }
+                }
+                User::Bot(ref mut b) => {
+                    let cmove = b.find_best_move(&mut board);
+                    if let Some(m) = cmove {
+                        board
+                            .make_move(&m)
+                            .expect("bot should only play legal moves");
+                    }
+                }
+            },
+        }
+
+        println!("\x1b[2J\x1b[H");
+        println!("{}", board);
+
+        match board.get_game_state() {
+            board::GameState::Draw => {
+                println!("DRAW");
+                break;
+            }
+            board::GameState::Win(board::Player::White) => {
+                println!("White Wins");
+                break;
+            }
+            board::GameState::Win(board::Player::Black) => {
+                println!("Black Wins");
+                break;
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,439 @@
+//! Training state written after every generation, in a small TOML-like format:
+//!
+//! ```text
+//! generation = 3
+//! seed = 1234
+//! depth = 4
+//! bot = "SEARCH all THREADS 2 DEFAULT"
+//! population = 16
+//! crossover = "section"
+//! ...
+//!
+//! [[bot]]
+//! score = 0.625
+//! rep = "+??Sv!!..."
+//!
+//! [[champion]]
+//! rep = "+??Sv!!..."
+//! ```
+//!
+//! Bots are sorted by score, best first, champions are the hall of fame,
+//! oldest first. Quotes and backslashes in strings are
+//! escaped with a backslash.
+//!
+//! SPSA and CMA-ES checkpoints have `optimizer = "spsa"` or `"cmaes"` and a
+//! `[[spsa]]` or `[[cmaes]]` table with the exact values they continue from,
+//! lists of them are written like `theta = [1.5, 12.25, ...]`. The bots are the
+//! ones that played the last generation.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use chlang::{evaluators::evaluator_0, game::Adjudication};
+
+use super::ga::{GaConfig, Genome, Individual};
+use super::optimizer::Kind;
+
+/// What an optimizer needs besides the population to continue where it stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptimizerState {
+    /// Everything is in the population and the hall of fame
+    Ga,
+    Spsa {
+        a: f64,
+        c: f64,
+        theta: Vec<f64>,
+        iteration: usize,
+    },
+    Cmaes {
+        mean: Vec<f64>,
+        sigma: f64,
+        variances: Vec<f64>,
+        sigma_path: Vec<f64>,
+        covariance_path: Vec<f64>,
+        generation: usize,
+    },
+}
+
+impl OptimizerState {
+    pub fn kind(&self) -> Kind {
+        match self {
+            OptimizerState::Ga => Kind::Ga,
+            OptimizerState::Spsa { .. } => Kind::Spsa,
+            OptimizerState::Cmaes { .. } => Kind::Cmaes,
+        }
+    }
+}
+
+pub struct Checkpoint {
+    /// Generation the population and scores are from
+    pub generation: usize,
+    /// Seed of the random number generator that breeds the next generation,
+    /// resuming with it plays the same games as an uninterrupted run
+    pub seed: u64,
+    pub depth: u8,
+    pub config: GaConfig,
+    /// Every individual with its fitness
+    pub population: Vec<(Individual, f64)>,
+    pub hall_of_fame: Vec<Genome>,
+    pub optimizer: OptimizerState,
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn rep(genome: &Genome) -> String {
+    quote(&genome.iter().map(|b| (b + 33) as char).collect::<String>())
+}
+
+fn list(values: &[f64]) -> String {
+    let values = values.iter().map(f64::to_string).collect::<Vec<_>>();
+    format!("[{}]", values.join(", "))
+}
+
+fn unquote(s: &str) -> Result<String, String> {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or(format!("{s} should be quoted"))?;
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.push(chars.next().ok_or("string ends with a backslash")?),
+            c => unquoted.push(c),
+        }
+    }
+    Ok(unquoted)
+}
+
+impl Display for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let config = &self.config;
+        writeln!(f, "generation = {}", self.generation)?;
+        writeln!(f, "seed = {}", self.seed)?;
+        writeln!(f, "depth = {}", self.depth)?;
+        writeln!(f, "bot = {}", quote(&config.bot))?;
+        writeln!(
+            f,
+            "optimizer = {}",
+            quote(&self.optimizer.kind().to_string())
+        )?;
+        writeln!(f, "population = {}", config.population)?;
+        if let Some(generations) = config.generations {
+            writeln!(f, "generations = {generations}")?;
+        }
+        writeln!(f, "games = {}", config.games)?;
+        writeln!(f, "tournament = {}", config.tournament_size)?;
+        writeln!(f, "elitism = {}", config.elitism)?;
+        writeln!(f, "crossover = {}", quote(&config.crossover.to_string()))?;
+        writeln!(f, "crossover_rate = {}", config.crossover_rate)?;
+        writeln!(f, "mutation_rate = {}", config.mutation_rate)?;
+        writeln!(f, "mutation = {}", quote(&config.mutation.to_string()))?;
+        writeln!(f, "mutation_strength = {}", config.mutation_strength)?;
+        writeln!(
+            f,
+            "section_steps = {}",
+            quote(&config.section_steps.to_string())
+        )?;
+        writeln!(f, "hall_of_fame = {}", config.hall_of_fame)?;
+        writeln!(f, "anchor_games = {}", config.anchor_games)?;
+        let adjudication = &config.adjudication;
+        if let Some(resign) = adjudication.resign {
+            writeln!(f, "resign = {}", quote(&resign.to_string()))?;
+        }
+        if let Some(draw) = adjudication.draw {
+            writeln!(f, "draw = {}", quote(&draw.to_string()))?;
+        }
+        if let Some(max_moves) = adjudication.max_moves {
+            writeln!(f, "max_moves = {max_moves}")?;
+        }
+        for (individual, score) in &self.population {
+            write!(
+                f,
+                "\n[[bot]]\nscore = {score}\nsigma = {}\nrep = {}\n",
+                individual.sigma,
+                rep(&individual.genome)
+            )?;
+        }
+        for genome in &self.hall_of_fame {
+            write!(f, "\n[[champion]]\nrep = {}\n", rep(genome))?;
+        }
+        match &self.optimizer {
+            OptimizerState::Ga => (),
+            OptimizerState::Spsa {
+                a,
+                c,
+                theta,
+                iteration,
+            } => {
+                writeln!(f, "\n[[spsa]]\na = {a}\nc = {c}\niteration = {iteration}")?;
+                writeln!(f, "theta = {}", list(theta))?;
+            }
+            OptimizerState::Cmaes {
+                mean,
+                sigma,
+                variances,
+                sigma_path,
+                covariance_path,
+                generation,
+            } => {
+                writeln!(f, "\n[[cmaes]]\ngeneration = {generation}\nsigma = {sigma}")?;
+                writeln!(f, "mean = {}", list(mean))?;
+                writeln!(f, "variances = {}", list(variances))?;
+                writeln!(f, "sigma_path = {}", list(sigma_path))?;
+                writeln!(f, "covariance_path = {}", list(covariance_path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Value of `key`, parsed
+fn field<T: FromStr>(fields: &HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    let value = fields.get(key).ok_or(format!("missing {key}"))?;
+    value.parse().map_err(|_| format!("invalid {key} {value}"))
+}
+
+/// Value of `key`, a quoted string, parsed
+fn quoted_field<T: FromStr>(fields: &HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    let value = unquote(fields.get(key).ok_or(format!("missing {key}"))?)?;
+    value.parse().map_err(|_| format!("invalid {key} {value}"))
+}
+
+/// Value of `key`, a list with a value for every byte of a bot
+fn list_field(fields: &HashMap<&str, &str>, key: &str) -> Result<Vec<f64>, String> {
+    let value = fields.get(key).ok_or(format!("missing {key}"))?;
+    let values = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or(format!("{key} should be a list"))?
+        .split(',')
+        .map(|v| v.trim().parse())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid {key} {value}"))?;
+    if values.len() != evaluator_0::BYTES {
+        return Err(format!("{key} should have {} values", evaluator_0::BYTES));
+    }
+    Ok(values)
+}
+
+impl FromStr for Checkpoint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The settings come first, then one table per bot
+        let mut tables = vec![("", HashMap::new())];
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+                tables.push((name, HashMap::new()));
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected key = value", n + 1))?;
+            tables
+                .last_mut()
+                .unwrap()
+                .1
+                .insert(key.trim(), value.trim());
+        }
+        let settings = &tables[0].1;
+
+        let default = GaConfig::default();
+        let config = GaConfig {
+            population: field(settings, "population")?,
+            generations: settings
+                .contains_key("generations")
+                .then(|| field(settings, "generations"))
+                .transpose()?,
+            games: field(settings, "games")?,
+            tournament_size: field(settings, "tournament")?,
+            elitism: field(settings, "elitism")?,
+            crossover: quoted_field(settings, "crossover")?,
+            crossover_rate: field(settings, "crossover_rate")?,
+            mutation_rate: field(settings, "mutation_rate")?,
+            mutation: quoted_field(settings, "mutation")?,
+            mutation_strength: field(settings, "mutation_strength")?,
+            section_steps: quoted_field(settings, "section_steps")?,
+            hall_of_fame: field(settings, "hall_of_fame")?,
+            anchor_games: field(settings, "anchor_games")?,
+            adjudication: Adjudication {
+                resign: settings
+                    .contains_key("resign")
+                    .then(|| quoted_field(settings, "resign"))
+                    .transpose()?,
+                draw: settings
+                    .contains_key("draw")
+                    .then(|| quoted_field(settings, "draw"))
+                    .transpose()?,
+                max_moves: settings
+                    .contains_key("max_moves")
+                    .then(|| field(settings, "max_moves"))
+                    .transpose()?,
+            },
+            concurrency: default.concurrency,
+            bot: unquote(settings.get("bot").ok_or("missing bot")?)?,
+            benchmark_interval: default.benchmark_interval,
+            benchmark_games: default.benchmark_games,
+        };
+
+        let mut population = vec![];
+        let mut hall_of_fame = vec![];
+        let mut state = None;
+        for (name, table) in &tables[1..] {
+            if matches!(*name, "spsa" | "cmaes") {
+                state = Some((*name, table));
+                continue;
+            }
+            let rep = unquote(table.get("rep").ok_or(format!("{name} without rep"))?)?;
+            if rep.len() != evaluator_0::BYTES || rep.bytes().any(|b| !(33..=126).contains(&b)) {
+                return Err(format!("invalid {name} {rep}"));
+            }
+            let genome = rep.bytes().map(|b| b - 33).collect();
+            match *name {
+                "bot" => {
+                    let sigma = field(table, "sigma")?;
+                    population.push((Individual { genome, sigma }, field(table, "score")?))
+                }
+                "champion" => hall_of_fame.push(genome),
+                _ => return Err(format!("unknown table {name}")),
+            }
+        }
+        if population.is_empty() {
+            return Err(String::from("checkpoint has no bots"));
+        }
+
+        // Checkpoints without an optimizer are from before there were others
+        let kind = match settings.contains_key("optimizer") {
+            true => quoted_field(settings, "optimizer")?,
+            false => Kind::Ga,
+        };
+        let optimizer = match (kind, state) {
+            (Kind::Ga, None) => OptimizerState::Ga,
+            (Kind::Spsa, Some(("spsa", table))) => OptimizerState::Spsa {
+                a: field(table, "a")?,
+                c: field(table, "c")?,
+                theta: list_field(table, "theta")?,
+                iteration: field(table, "iteration")?,
+            },
+            (Kind::Cmaes, Some(("cmaes", table))) => OptimizerState::Cmaes {
+                mean: list_field(table, "mean")?,
+                sigma: field(table, "sigma")?,
+                variances: list_field(table, "variances")?,
+                sigma_path: list_field(table, "sigma_path")?,
+                covariance_path: list_field(table, "covariance_path")?,
+                generation: field(table, "generation")?,
+            },
+            (kind, _) => return Err(format!("state doesn't match the optimizer {kind}")),
+        };
+
+        Ok(Checkpoint {
+            generation: field(settings, "generation")?,
+            seed: field(settings, "seed")?,
+            depth: field(settings, "depth")?,
+            config,
+            population,
+            hall_of_fame,
+            optimizer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::Crossover;
+
+    fn checkpoint(optimizer: OptimizerState) -> Checkpoint {
+        Checkpoint {
+            generation: 7,
+            seed: u64::MAX,
+            depth: 3,
+            config: GaConfig {
+                generations: Some(20),
+                crossover: Crossover::Uniform,
+                mutation_rate: 0.125,
+                bot: String::from("SEARCH all THREADS 2 BOOK \"my \\book\" DEFAULT"),
+                ..GaConfig::default()
+            },
+            population: vec![
+                (
+                    Individual {
+                        genome: vec![93; evaluator_0::BYTES],
+                        sigma: 2.5,
+                    },
+                    0.75,
+                ),
+                (
+                    Individual {
+                        genome: vec![0; evaluator_0::BYTES],
+                        sigma: 0.5,
+                    },
+                    -0.5,
+                ),
+            ],
+            hall_of_fame: vec![vec![1; evaluator_0::BYTES]],
+            optimizer,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let checkpoint = checkpoint(OptimizerState::Ga);
+        let text = checkpoint.to_string();
+        let parsed = text.parse::<Checkpoint>().unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.generation, 7);
+        assert_eq!(parsed.seed, u64::MAX);
+        assert_eq!(parsed.config.bot, checkpoint.config.bot);
+        assert_eq!(parsed.population, checkpoint.population);
+        assert_eq!(parsed.hall_of_fame, checkpoint.hall_of_fame);
+        assert_eq!(parsed.optimizer, OptimizerState::Ga);
+
+        // Older checkpoints are all from the genetic algorithm
+        let older = text.replace("optimizer = \"ga\"\n", "");
+        assert_eq!(
+            older.parse::<Checkpoint>().unwrap().optimizer,
+            OptimizerState::Ga
+        );
+    }
+
+    #[test]
+    fn optimizer_state_round_trip() {
+        let values = |start: f64| {
+            (0..evaluator_0::BYTES)
+                .map(|i| start + i as f64 / 3.0)
+                .collect::<Vec<_>>()
+        };
+        for optimizer in [
+            OptimizerState::Spsa {
+                a: 100.0,
+                c: 0.1 + 0.2,
+                theta: values(0.1),
+                iteration: 12,
+            },
+            OptimizerState::Cmaes {
+                mean: values(0.7),
+                sigma: 1e-300,
+                variances: values(1.0),
+                sigma_path: values(-4.2),
+                covariance_path: values(-0.01),
+                generation: 5,
+            },
+        ] {
+            let checkpoint = checkpoint(optimizer);
+            let text = checkpoint.to_string();
+            let parsed = text.parse::<Checkpoint>().unwrap();
+            assert_eq!(parsed.optimizer, checkpoint.optimizer);
+            assert_eq!(parsed.to_string(), text);
+
+            let kind = format!("optimizer = \"{}\"", checkpoint.optimizer.kind());
+            let ga = text.replace(&kind, "optimizer = \"ga\"");
+            assert!(ga.parse::<Checkpoint>().is_err());
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Covariance matrix adaptation evolution strategy. Every generation bots are
+//! sampled from a normal distribution around the mean values, and the mean,
+//! step size and variances move towards the bots that scored best.
+//!
+//! Only the diagonal of the covariance matrix is adapted (sep-CMA-ES by Ros
+//! and Hansen), the full matrix would have over 80000 entries to learn from a
+//! few noisy games per generation.
+
+use std::error::Error;
+
+use chlang::{evaluators::evaluator_0, tree_evaluator::Bot};
+use rand::rngs::StdRng;
+
+use super::checkpoint::{Checkpoint, OptimizerState};
+use super::ga::{
+    anchors, evaluate, gaussian, rounded, GaConfig, GameTotals, Genome, Individual, MAX_VALUE,
+};
+use super::optimizer::Optimizer;
+
+pub struct Cmaes {
+    config: GaConfig,
+    anchors: Vec<Bot>,
+    mean: Vec<f64>,
+    sigma: f64,
+    /// Diagonal of the covariance matrix
+    variances: Vec<f64>,
+    /// Evolution paths of the step size and of the covariance
+    sigma_path: Vec<f64>,
+    covariance_path: Vec<f64>,
+    generation: usize,
+    /// Recombination weights of the best half of the samples, summing to 1
+    weights: Vec<f64>,
+    mu_eff: f64,
+    /// The samples of the last generation with their fitness, best first
+    ranked: Vec<(Individual, f64)>,
+}
+
+impl Cmaes {
+    /// `config.population` bots are sampled every generation, the step size
+    /// starts at `config.mutation_strength`
+    pub fn new(template: &Bot, start: &Genome, config: &GaConfig) -> Result<Cmaes, Box<dyn Error>> {
+        let mu = config.population / 2;
+        let weights = (1..=mu)
+            .map(|i| (mu as f64 + 0.5).ln() - (i as f64).ln())
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<f64>();
+        let weights = weights.iter().map(|w| w / total).collect::<Vec<_>>();
+        let mu_eff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+
+        let n = evaluator_0::BYTES;
+        Ok(Cmaes {
+            config: config.clone(),
+            anchors: match config.anchor_games {
+                0 => vec![],
+                _ => anchors(template),
+            },
+            mean: start.iter().map(|b| *b as f64).collect(),
+            sigma: config.mutation_strength as f64,
+            variances: vec![1.0; n],
+            sigma_path: vec![0.0; n],
+            covariance_path: vec![0.0; n],
+            generation: 0,
+            weights,
+            mu_eff,
+            ranked: vec![],
+        })
+    }
+
+    /// Continue with the distribution and evolution paths of a checkpoint
+    pub fn resume(
+        template: &Bot,
+        checkpoint: Checkpoint,
+        config: &GaConfig,
+    ) -> Result<Cmaes, Box<dyn Error>> {
+        let OptimizerState::Cmaes {
+            mean,
+            sigma,
+            variances,
+            sigma_path,
+            covariance_path,
+            generation,
+        } = checkpoint.optimizer
+        else {
+            Err("not a CMA-ES checkpoint")?
+        };
+        // The weights only depend on the config
+        let mut cmaes = Cmaes::new(template, &rounded(&mean), config)?;
+        cmaes.mean = mean;
+        cmaes.sigma = sigma;
+        cmaes.variances = variances;
+        cmaes.sigma_path = sigma_path;
+        cmaes.covariance_path = covariance_path;
+        cmaes.generation = generation;
+        cmaes.ranked = checkpoint.population;
+        Ok(cmaes)
+    }
+}
+
+impl Optimizer for Cmaes {
+    fn step(&mut self, template: &Bot, rng: &mut StdRng) -> (Vec<f64>, GameTotals) {
+        let n = self.mean.len() as f64;
+        let mu_eff = self.mu_eff;
+
+        // Sample around the mean, z is the standard normal part of each sample
+        let samples = (0..self.config.population)
+            .map(|_| {
+                (0..self.mean.len())
+                    .map(|_| gaussian(rng))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let steps = samples
+            .iter()
+            .map(|z| {
+                z.iter()
+                    .zip(&self.variances)
+                    .map(|(z, v)| z * v.sqrt())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let population = steps
+            .iter()
+            .map(|y| {
+                let values = self
+                    .mean
+                    .iter()
+                    .zip(y)
+                    .map(|(m, y)| m + self.sigma * y)
+                    .collect::<Vec<_>>();
+                Individual {
+                    genome: rounded(&values),
+                    sigma: self.sigma,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let opponents = self
+            .anchors
+            .iter()
+            .map(|b| b.bot_clone())
+            .collect::<Vec<_>>();
+        let (fitness, totals) = evaluate(template, &population, &opponents, &self.config, rng);
+        let mut ranked = (0..population.len()).collect::<Vec<_>>();
+        ranked.sort_by(|a, b| fitness[*b].total_cmp(&fitness[*a]));
+
+        // Weighted averages of the best samples
+        let weighted = |vectors: &[Vec<f64>]| {
+            let mut average = vec![0.0; self.mean.len()];
+            for (w, i) in self.weights.iter().zip(&ranked) {
+                for (a, x) in average.iter_mut().zip(&vectors[*i]) {
+                    *a += w * x;
+                }
+            }
+            average
+        };
+        let z_w = weighted(&samples);
+        let y_w = weighted(&steps);
+
+        for (m, y) in self.mean.iter_mut().zip(&y_w) {
+            *m = (*m + self.sigma * y).clamp(0.0, MAX_VALUE as f64);
+        }
+
+        // Learning rates, with the faster covariance learning of sep-CMA-ES
+        let c_sigma = (mu_eff + 2.0) / (n + mu_eff + 5.0);
+        let d_sigma = 1.0 + 2.0 * (((mu_eff - 1.0) / (n + 1.0)).sqrt() - 1.0).max(0.0) + c_sigma;
+        let c_c = (4.0 + mu_eff / n) / (n + 4.0 + 2.0 * mu_eff / n);
+        let c_1 = 2.0 / ((n + 1.3).powi(2) + mu_eff) * (n + 2.0) / 3.0;
+        let c_mu = (2.0 * (mu_eff - 2.0 + 1.0 / mu_eff) / ((n + 2.0).powi(2) + mu_eff) * (n + 2.0)
+            / 3.0)
+            .min(1.0 - c_1);
+        // Expected length of a standard normal vector
+        let chi_n = n.sqrt() * (1.0 - 1.0 / (4.0 * n) + 1.0 / (21.0 * n * n));
+
+        for (p, z) in self.sigma_path.iter_mut().zip(&z_w) {
+            *p = (1.0 - c_sigma) * *p + (c_sigma * (2.0 - c_sigma) * mu_eff).sqrt() * z;
+        }
+        let sigma_path_length = self.sigma_path.iter().map(|p| p * p).sum::<f64>().sqrt();
+        let generations = (self.generation + 1) as i32;
+        let h_sigma = sigma_path_length / (1.0 - (1.0 - c_sigma).powi(2 * generations)).sqrt()
+            < (1.4 + 2.0 / (n + 1.0)) * chi_n;
+        let h_sigma = if h_sigma { 1.0 } else { 0.0 };
+
+        for (p, y) in self.covariance_path.iter_mut().zip(&y_w) {
+            *p = (1.0 - c_c) * *p + h_sigma * (c_c * (2.0 - c_c) * mu_eff).sqrt() * y;
+        }
+        for (j, v) in self.variances.iter_mut().enumerate() {
+            let rank_mu = self
+                .weights
+                .iter()
+                .zip(&ranked)
+                .map(|(w, i)| w * steps[*i][j].powi(2))
+                .sum::<f64>();
+            let p = self.covariance_path[j];
+            *v = (1.0 - c_1 - c_mu) * *v
+                + c_1 * (p * p + (1.0 - h_sigma) * c_c * (2.0 - c_c) * *v)
+                + c_mu * rank_mu;
+        }
+        self.sigma *= ((c_sigma / d_sigma) * (sigma_path_length / chi_n - 1.0)).exp();
+        self.sigma = self.sigma.min(MAX_VALUE as f64);
+        self.generation += 1;
+
+        self.ranked = ranked
+            .iter()
+            .map(|i| (population[*i].clone(), fitness[*i]))
+            .collect();
+
+        (fitness, totals)
+    }
+
+    fn best(&self) -> Genome {
+        rounded(&self.mean)
+    }
+
+    fn checkpoint(&self, generation: usize, seed: u64, template: &Bot) -> String {
+        Checkpoint {
+            generation,
+            seed,
+            depth: template.search_depth,
+            config: self.config.clone(),
+            population: self.ranked.clone(),
+            hall_of_fame: vec![],
+            optimizer: OptimizerState::Cmaes {
+                mean: self.mean.clone(),
+                sigma: self.sigma,
+                variances: self.variances.clone(),
+                sigma_path: self.sigma_path.clone(),
+                covariance_path: self.covariance_path.clone(),
+                generation: self.generation,
+            },
+        }
+        .to_string()
+    }
+}
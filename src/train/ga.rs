@@ -0,0 +1,621 @@
+//! Genetic algorithm over the bytes of `evaluator_0` bots
+
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use super::checkpoint::{Checkpoint, OptimizerState};
+use super::optimizer::Optimizer;
+
+use chlang::{
+    board::{Board, GameState, Player},
+    evaluators::{evaluator_0, material_evaluator::MaterialEvaluator, NoneEvaluator},
+    game::{self, Adjudication},
+    pool,
+    stats::MatchScore,
+    tree_evaluator::{Bot, Eval},
+    User,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Values of an `evaluator_0::Evaluator`, without the offset of its string representation
+pub type Genome = Vec<u8>;
+
+/// Highest value a byte can have and still be printable in the string representation
+pub const MAX_VALUE: u8 = 126 - 33;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossover {
+    /// Every byte comes from either parent
+    Uniform,
+    /// Every field (piece values, a piece's position table, ...) comes from either
+    /// parent, see `Eval::crossover`
+    Section,
+}
+
+impl FromStr for Crossover {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Crossover::Uniform),
+            "section" => Ok(Crossover::Section),
+            _ => Err("crossover must be uniform or section"),
+        }
+    }
+}
+
+impl Display for Crossover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Crossover::Uniform => write!(f, "uniform"),
+            Crossover::Section => write!(f, "section"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    /// Uniformly distributed changes of at most `mutation_strength`
+    Uniform,
+    /// Normally distributed changes with `mutation_strength` as standard deviation
+    Gaussian,
+    /// Normally distributed changes with the standard deviation of the byte's section
+    Section,
+    /// Normally distributed changes with a standard deviation that every
+    /// individual carries and that is mutated itself, like in evolution strategies
+    Adaptive,
+}
+
+impl FromStr for Mutation {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Mutation::Uniform),
+            "gaussian" => Ok(Mutation::Gaussian),
+            "section" => Ok(Mutation::Section),
+            "adaptive" => Ok(Mutation::Adaptive),
+            _ => Err("mutation must be uniform, gaussian, section or adaptive"),
+        }
+    }
+}
+
+impl Display for Mutation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mutation::Uniform => write!(f, "uniform"),
+            Mutation::Gaussian => write!(f, "gaussian"),
+            Mutation::Section => write!(f, "section"),
+            Mutation::Adaptive => write!(f, "adaptive"),
+        }
+    }
+}
+
+/// Standard deviation of changes for every section of `evaluator_0`, in the
+/// order of `evaluator_0::SECTIONS`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SectionSteps(pub [f64; 5]);
+
+/// Comma separated, e.g. `5,1,2,2,1`
+impl FromStr for SectionSteps {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "section steps must be numbers")?;
+        let steps = steps
+            .try_into()
+            .map_err(|_| "there must be a step for each of the 5 sections")?;
+        Ok(SectionSteps(steps))
+    }
+}
+
+impl Display for SectionSteps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let steps = self.0.map(|s| s.to_string());
+        write!(f, "{}", steps.join(","))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GaConfig {
+    pub population: usize,
+    /// Train forever if not set
+    pub generations: Option<usize>,
+    /// Games every individual plays per generation against random opponents
+    pub games: usize,
+    pub tournament_size: usize,
+    /// Number of best individuals copied unchanged into the next generation
+    pub elitism: usize,
+    pub crossover: Crossover,
+    /// Probability that a child has two parents instead of being a copy of one
+    pub crossover_rate: f64,
+    /// Probability that a byte is mutated
+    pub mutation_rate: f64,
+    pub mutation: Mutation,
+    /// Largest change of a mutated byte for uniform mutation, the standard
+    /// deviation for gaussian mutation and the starting one for adaptive mutation
+    pub mutation_strength: u8,
+    pub section_steps: SectionSteps,
+    /// Number of past best bots kept to play against
+    pub hall_of_fame: usize,
+    /// Games every individual plays against each anchor and each bot in the
+    /// hall of fame, none if 0
+    pub anchor_games: usize,
+    /// Ending games early, not at all by default
+    pub adjudication: Adjudication,
+    pub concurrency: usize,
+    /// Specification of the bot every individual is, with its own evaluator.
+    /// Checkpoints keep it so that a resumed run searches the same way.
+    pub bot: String,
+    /// Play the best bot against the default evaluator every this many
+    /// generations, never if 0
+    pub benchmark_interval: usize,
+    pub benchmark_games: usize,
+}
+
+impl Default for GaConfig {
+    fn default() -> Self {
+        Self {
+            population: 16,
+            generations: None,
+            games: 4,
+            tournament_size: 3,
+            elitism: 2,
+            crossover: Crossover::Section,
+            crossover_rate: 0.7,
+            mutation_rate: 0.05,
+            mutation: Mutation::Uniform,
+            mutation_strength: 5,
+            section_steps: SectionSteps([5.0, 1.0, 2.0, 2.0, 1.0]),
+            hall_of_fame: 4,
+            anchor_games: 2,
+            adjudication: Adjudication::default(),
+            concurrency: 1,
+            bot: String::from("DEFAULT"),
+            benchmark_interval: 5,
+            benchmark_games: 10,
+        }
+    }
+}
+
+/// A genome with its own standard deviation for adaptive mutation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Individual {
+    pub genome: Genome,
+    pub sigma: f64,
+}
+
+/// Totals over all games of a generation
+#[derive(Default)]
+pub struct GameTotals {
+    pub games: usize,
+    pub draws: usize,
+    pub plies: usize,
+}
+
+/// The genome of a bot, only `evaluator_0` bots can be trained
+pub fn genome(bot: &Bot) -> Result<Genome, String> {
+    let rep = bot.evaluator.string_rep();
+    if rep.len() != evaluator_0::BYTES {
+        return Err(format!(
+            "only evaluator_0 bots can be trained, {rep:?} is not one"
+        ));
+    }
+    Ok(rep.bytes().map(|b| b - 33).collect())
+}
+
+/// A bot searching like `template` but evaluating with `genome`
+pub fn bot(template: &Bot, genome: &Genome) -> Bot {
+    let mut bot = template.bot_clone();
+    bot.evaluator = Box::new(evaluator_0::Evaluator::from(genome.as_slice()));
+    bot
+}
+
+/// Genome closest to real valued bytes
+pub fn rounded(values: &[f64]) -> Genome {
+    values
+        .iter()
+        .map(|v| v.round().clamp(0.0, MAX_VALUE as f64) as u8)
+        .collect()
+}
+
+/// Sample of the standard normal distribution (Box-Muller transform)
+pub fn gaussian(rng: &mut StdRng) -> f64 {
+    let u = rng.gen_range(f64::EPSILON..1.0);
+    let v = rng.gen::<f64>();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+/// Change every byte with probability `mutation_rate`. The bytes always stay
+/// between 0 and `MAX_VALUE`, so the string representation stays printable.
+pub fn mutate(individual: &mut Individual, config: &GaConfig, rng: &mut StdRng) {
+    if config.mutation == Mutation::Adaptive {
+        // Log-normal change of the step size before it is used
+        let tau = 1.0 / (evaluator_0::BYTES as f64).sqrt();
+        individual.sigma =
+            (individual.sigma * (tau * gaussian(rng)).exp()).clamp(0.5, MAX_VALUE as f64);
+    }
+    let strength = config.mutation_strength as f64;
+    let mut start = 0;
+    for (section, len) in evaluator_0::SECTIONS.iter().enumerate() {
+        for b in &mut individual.genome[start..start + len] {
+            if !rng.gen_bool(config.mutation_rate) {
+                continue;
+            }
+            let change = match config.mutation {
+                Mutation::Uniform => {
+                    let strength = config.mutation_strength as i32;
+                    rng.gen_range(-strength..=strength) as f64
+                }
+                Mutation::Gaussian => gaussian(rng) * strength,
+                Mutation::Section => gaussian(rng) * config.section_steps.0[section],
+                Mutation::Adaptive => gaussian(rng) * individual.sigma,
+            };
+            *b = (*b as f64 + change).round().clamp(0.0, MAX_VALUE as f64) as u8;
+        }
+        start += len;
+    }
+}
+
+pub fn crossover(a: &Genome, b: &Genome, kind: Crossover, rng: &mut StdRng) -> Genome {
+    match kind {
+        Crossover::Uniform => a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| if rng.gen_bool(0.5) { *x } else { *y })
+            .collect(),
+        Crossover::Section => {
+            let a = evaluator_0::Evaluator::from(a.as_slice());
+            let b = evaluator_0::Evaluator::from(b.as_slice());
+            let child = a.crossover(&b, rng);
+            child.string_rep().bytes().map(|x| x - 33).collect()
+        }
+    }
+}
+
+/// Fixed opponents for every individual, so that bots can't get fitter by
+/// only exploiting the quirks of each other: the default bot, a material bot
+/// and a random bot. They search like `template`, the random bot only one
+/// ply deep.
+pub fn anchors(template: &Bot) -> Vec<Bot> {
+    let anchor = |evaluator: Box<dyn Eval + Sync + Send>| {
+        let mut bot = template.bot_clone();
+        bot.evaluator = evaluator;
+        bot
+    };
+    let mut random = anchor(Box::new(NoneEvaluator));
+    random.search_depth = 1;
+    vec![
+        anchor(Box::<evaluator_0::Evaluator>::default()),
+        anchor(Box::<MaterialEvaluator>::default()),
+        random,
+    ]
+}
+
+/// Pick the fittest of `tournament_size` random individuals
+fn tournament<'a>(
+    population: &'a [Individual],
+    fitness: &[f64],
+    size: usize,
+    rng: &mut StdRng,
+) -> &'a Individual {
+    let best = (0..size.max(1))
+        .map(|_| rng.gen_range(0..population.len()))
+        .max_by(|a, b| fitness[*a].total_cmp(&fitness[*b]))
+        .unwrap();
+    &population[best]
+}
+
+/// Fill a population from the seed genomes and mutated copies of them
+pub fn initial_population(
+    seeds: &[Genome],
+    config: &GaConfig,
+    rng: &mut StdRng,
+) -> Vec<Individual> {
+    let mut population = seeds
+        .iter()
+        .map(|genome| Individual {
+            genome: genome.clone(),
+            sigma: config.mutation_strength as f64,
+        })
+        .collect::<Vec<_>>();
+    population.truncate(config.population);
+    while population.len() < config.population {
+        let mut individual = population[population.len() % seeds.len()].clone();
+        mutate(&mut individual, config, rng);
+        population.push(individual);
+    }
+    population
+}
+
+/// Average points per game of every individual (1 for a win, 0.5 for a draw)
+/// over its games against the population and against `opponents`
+pub fn evaluate(
+    template: &Bot,
+    population: &[Individual],
+    opponents: &[Bot],
+    config: &GaConfig,
+    rng: &mut StdRng,
+) -> (Vec<f64>, GameTotals) {
+    // The opponents come after the individuals
+    let bots = population
+        .iter()
+        .map(|i| bot(template, &i.genome))
+        .chain(opponents.iter().map(|b| b.bot_clone()))
+        .collect::<Vec<_>>();
+
+    // Every individual plays its games against random opponents, alternating colors
+    let mut games = vec![];
+    for i in 0..population.len() {
+        for g in 0..config.games {
+            let mut j = rng.gen_range(0..population.len() - 1);
+            if j >= i {
+                j += 1;
+            }
+            let (white, black) = if g % 2 == 0 { (i, j) } else { (j, i) };
+            games.push((white, black, rng.gen::<u64>()));
+        }
+        for j in population.len()..bots.len() {
+            for g in 0..config.anchor_games {
+                let (white, black) = if g % 2 == 0 { (i, j) } else { (j, i) };
+                games.push((white, black, rng.gen::<u64>()));
+            }
+        }
+    }
+
+    let mut points = vec![0.0; bots.len()];
+    let mut played = vec![0; bots.len()];
+    let mut totals = GameTotals::default();
+    let bots = &bots;
+    pool::run(
+        games,
+        config.concurrency,
+        |(white, black, seed)| {
+            let mut wp = User::Bot(Box::new(bots[white].bot_clone()));
+            let mut bp = User::Bot(Box::new(bots[black].bot_clone()));
+            let mut plies = 0;
+            let mut rng = StdRng::seed_from_u64(seed);
+            let outcome = game::run_with(
+                Board::default(),
+                &mut wp,
+                &mut bp,
+                &config.adjudication,
+                None,
+                &mut rng,
+                |_, _| plies += 1,
+            );
+            (white, black, outcome.state, plies)
+        },
+        |(white, black, result, plies)| {
+            match result {
+                GameState::Win(Player::White) => points[white] += 1.0,
+                GameState::Win(Player::Black) => points[black] += 1.0,
+                _ => {
+                    points[white] += 0.5;
+                    points[black] += 0.5;
+                    totals.draws += 1;
+                }
+            }
+            totals.games += 1;
+            totals.plies += plies;
+            played[white] += 1;
+            played[black] += 1;
+            true
+        },
+    );
+
+    let fitness = points
+        .iter()
+        .zip(played)
+        .take(population.len())
+        .map(|(p, n)| if n == 0 { 0.0 } else { p / n as f64 })
+        .collect();
+    (fitness, totals)
+}
+
+/// Play `genome` against the default `evaluator_0` bot with the same search,
+/// alternating colors. The score is from the perspective of `genome`.
+pub fn benchmark(
+    template: &Bot,
+    genome: &Genome,
+    config: &GaConfig,
+    rng: &mut StdRng,
+) -> MatchScore {
+    let champion = bot(template, genome);
+    let mut baseline = template.bot_clone();
+    baseline.evaluator = Box::new(evaluator_0::Evaluator::default());
+
+    let games = (0..config.benchmark_games)
+        .map(|i| (i % 2 == 1, rng.gen::<u64>()))
+        .collect::<Vec<_>>();
+    let mut score = MatchScore::default();
+    let (champion, baseline) = (&champion, &baseline);
+    pool::run(
+        games,
+        config.concurrency,
+        |(swapped, seed)| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (mut c, mut b) = (
+                User::Bot(Box::new(champion.bot_clone())),
+                User::Bot(Box::new(baseline.bot_clone())),
+            );
+            let (white, black) = match swapped {
+                false => (&mut c, &mut b),
+                true => (&mut b, &mut c),
+            };
+            let outcome = game::run_with(
+                Board::default(),
+                white,
+                black,
+                &config.adjudication,
+                None,
+                &mut rng,
+                |_, _| (),
+            );
+            (swapped, outcome.state)
+        },
+        |(swapped, result)| {
+            match (result, swapped) {
+                (GameState::Win(Player::White), false) | (GameState::Win(Player::Black), true) => {
+                    score.wins += 1
+                }
+                (GameState::Win(_), _) => score.losses += 1,
+                _ => score.draws += 1,
+            }
+            true
+        },
+    );
+    score
+}
+
+/// Elites are kept, the rest are children of tournament winners
+pub fn next_generation(
+    population: &[Individual],
+    fitness: &[f64],
+    config: &GaConfig,
+    rng: &mut StdRng,
+) -> Vec<Individual> {
+    let mut ranked = (0..population.len()).collect::<Vec<_>>();
+    ranked.sort_by(|a, b| fitness[*b].total_cmp(&fitness[*a]));
+
+    let mut next = ranked
+        .iter()
+        .take(config.elitism.min(config.population))
+        .map(|i| population[*i].clone())
+        .collect::<Vec<_>>();
+    while next.len() < config.population {
+        let a = tournament(population, fitness, config.tournament_size, rng);
+        let mut child = if rng.gen_bool(config.crossover_rate) {
+            let b = tournament(population, fitness, config.tournament_size, rng);
+            Individual {
+                genome: crossover(&a.genome, &b.genome, config.crossover, rng),
+                sigma: (a.sigma + b.sigma) / 2.0,
+            }
+        } else {
+            a.clone()
+        };
+        mutate(&mut child, config, rng);
+        next.push(child);
+    }
+    next
+}
+
+/// The genetic algorithm as an `Optimizer`
+pub struct Ga {
+    config: GaConfig,
+    population: Vec<Individual>,
+    /// Best bots of earlier generations, oldest first
+    hall_of_fame: Vec<Genome>,
+    anchors: Vec<Bot>,
+    /// The last generation sorted by fitness, the next one is bred from it
+    ranked: Vec<(Individual, f64)>,
+}
+
+impl Ga {
+    pub fn new(
+        template: &Bot,
+        population: Vec<Individual>,
+        config: &GaConfig,
+    ) -> Result<Ga, Box<dyn Error>> {
+        Ok(Ga {
+            config: config.clone(),
+            population,
+            hall_of_fame: vec![],
+            anchors: match config.anchor_games {
+                0 => vec![],
+                _ => anchors(template),
+            },
+            ranked: vec![],
+        })
+    }
+
+    /// Continue after the generation of the checkpoint
+    pub fn resume(
+        template: &Bot,
+        checkpoint: Checkpoint,
+        config: &GaConfig,
+    ) -> Result<Ga, Box<dyn Error>> {
+        let mut ga = Ga::new(template, vec![], config)?;
+        ga.hall_of_fame = checkpoint.hall_of_fame;
+        ga.ranked = checkpoint.population;
+        Ok(ga)
+    }
+}
+
+impl Optimizer for Ga {
+    fn step(&mut self, template: &Bot, rng: &mut StdRng) -> (Vec<f64>, GameTotals) {
+        let config = &self.config;
+        if !self.ranked.is_empty() {
+            let (ranked, fitness) = self.ranked.iter().cloned().unzip::<_, _, Vec<_>, Vec<_>>();
+            self.population = next_generation(&ranked, &fitness, config, rng);
+        }
+
+        let opponents = match config.anchor_games {
+            0 => vec![],
+            _ => self
+                .anchors
+                .iter()
+                .map(|b| b.bot_clone())
+                .chain(self.hall_of_fame.iter().map(|g| bot(template, g)))
+                .collect(),
+        };
+        let (fitness, totals) = evaluate(template, &self.population, &opponents, config, rng);
+
+        let mut ranked = (0..self.population.len()).collect::<Vec<_>>();
+        ranked.sort_by(|a, b| fitness[*b].total_cmp(&fitness[*a]));
+        self.ranked = ranked
+            .iter()
+            .map(|i| (self.population[*i].clone(), fitness[*i]))
+            .collect();
+
+        let best = self.best();
+        if config.hall_of_fame > 0 && !self.hall_of_fame.contains(&best) {
+            self.hall_of_fame.push(best);
+            if self.hall_of_fame.len() > config.hall_of_fame {
+                self.hall_of_fame.remove(0);
+            }
+        }
+        (fitness, totals)
+    }
+
+    fn best(&self) -> Genome {
+        self.ranked[0].0.genome.clone()
+    }
+
+    fn checkpoint(&self, generation: usize, seed: u64, template: &Bot) -> String {
+        Checkpoint {
+            generation,
+            seed,
+            depth: template.search_depth,
+            config: self.config.clone(),
+            population: self.ranked.clone(),
+            hall_of_fame: self.hall_of_fame.clone(),
+            optimizer: OptimizerState::Ga,
+        }
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors_search_like_template() {
+        let mut template = Bot::new(Box::<evaluator_0::Evaluator>::default(), 3);
+        template.options = "all".parse().unwrap();
+        template.threads = 2;
+        let anchors = anchors(&template);
+        assert_eq!(anchors.len(), 3);
+        for anchor in &anchors {
+            assert_eq!(anchor.options, template.options);
+            assert_eq!(anchor.threads, template.threads);
+        }
+        let depths = anchors.iter().map(|b| b.search_depth).collect::<Vec<_>>();
+        assert_eq!(depths, [3, 3, 1]);
+        assert_eq!(
+            anchors[1].evaluator.string_rep(),
+            MaterialEvaluator::default().string_rep()
+        );
+    }
+}
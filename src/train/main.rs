@@ -1,270 +1,247 @@
-use std::fs::write;
-use std::io::BufRead;
+use std::fs::read_to_string;
+
+use chlang::{cli, game::Adjudication};
+use rand::{rngs::StdRng, SeedableRng};
+
+mod checkpoint;
+mod cmaes;
+mod ga;
+mod metrics;
+mod optimizer;
+mod spsa;
+
+use checkpoint::{Checkpoint, OptimizerState};
+use cmaes::Cmaes;
+use ga::{Ga, GaConfig};
+use metrics::Log;
+use optimizer::{Kind, Optimizer};
+use spsa::{Spsa, SpsaConfig};
+
+const HELP: &str = "Train evaluator_0 bots with a genetic algorithm, SPSA or CMA-ES
+
+Usage: train --checkpoint <FILE> [OPTIONS]
+
+Options:
+  --checkpoint <FILE>        Where the population is saved after every generation
+  --resume <FILE>            Continue from a checkpoint, with its settings unless they are
+                             given again. Files with only bot strings start a new population.
+  --optimizer <KIND>         How the bots are improved [default: ga]
+                               ga: genetic algorithm, the checkpoint has the population
+                               spsa: a pair of opposite changes to all bytes plays every
+                                 generation, the winner's direction is followed
+                               cmaes: a population is sampled around the mean bytes,
+                                 which move towards the best samples
+                             Resuming continues with the optimizer of the checkpoint.
+  --bot1 <BOT>               First bot to start from, all bots search like it [default: DEFAULT]
+  --bot2 <BOT>               Second bot to start from [default: DEFAULT]
+  --depth <N>                Search depth of the bots, overriding the bot's own
+  --population <N>           Number of bots per generation, also of CMA-ES [default: 16]
+  --generations <N>          Stop after this many generations [default: never]
+  --games <N>                Games per bot and generation [default: 4]
+  --tournament <N>           Tournament size for selecting parents [default: 3]
+  --elitism <N>              Best bots kept unchanged [default: 2]
+  --crossover <KIND>         uniform or section [default: section]
+  --crossover-rate <P>       Probability of a child having two parents [default: 0.7]
+  --mutation-rate <P>        Probability of mutating a byte [default: 0.05]
+  --mutation <KIND>          How bytes are changed [default: uniform]
+                               uniform: by at most the mutation strength
+                               gaussian: normally distributed with the mutation strength
+                                 as standard deviation
+                               section: normally distributed with the section steps
+                               adaptive: normally distributed with a standard deviation
+                                 that evolves with every bot, starting at the strength
+  --mutation-strength <N>    Size of the changes of a mutated byte, and the initial step
+                             size of CMA-ES [default: 5]
+  --section-steps <STEPS>    Standard deviations for piece values, positions, attacks,
+                             castling and moves [default: 5,1,2,2,1]
+  --hall-of-fame <N>         Number of past best bots every bot also plays [default: 4]
+  --anchor-games <N>         Games against every past best bot and every anchor (DEFAULT,
+                             MATERIAL and RANDOM), 0 to only play each other [default: 2]
+  --resign <SCORE,MOVES>     Adjudicate a win when both bots' scores are beyond SCORE for
+                             MOVES moves in a row
+  --draw <SCORE,MOVES,AFTER> Adjudicate a draw when both bots' scores are within SCORE of 0
+                             for MOVES moves in a row after move AFTER
+  --max-moves <N>            Adjudicate a draw after N moves
+  --spsa-a <A>               Step size of SPSA [default: 100]
+  --spsa-c <C>               Size of the SPSA changes to every byte [default: 4]
+  --concurrency <N>          Number of games played at once [default: 1]
+  --log <FILE>               Append statistics of every generation to a CSV file
+  --benchmark-every <N>      Play the best bot against the default evaluator every N
+                             generations, 0 to never do it [default: 5]
+  --benchmark-games <N>      Games of every benchmark [default: 10]
+  --seed <N>                 Seed for reproducible training
+  --help                     Print this help";
 
-use chlang::{
-    board::{Board, GameState, Player},
-    cmove::Move,
-    game,
-    tree_evaluator::Bot,
-    User,
-};
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // skip name of program
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if cli::take_switch(&mut args, "--help") {
+        println!("{HELP}");
+        return Ok(());
+    }
 
-pub fn train(mut b1: Bot, mut b2: Bot, checkpoint_path: String) {
-    let mut contenders = [b1.bot_clone(), b2.bot_clone(), b1.modified(), b2.modified()];
-    loop {
-        let mut scores: [usize; 4] = [0, 0, 0, 0];
-        for i in 0..4 {
-            for j in 0..4 {
-                if i == j {
-                    continue;
-                }
-                let mut wp = User::Bot(contenders[i].bot_clone());
-                let mut bp = User::Bot(contenders[j].bot_clone());
-                match game::run(&mut wp, &mut bp) {
-                    GameState::Win(Player::White) => scores[i] += 2,
-                    GameState::Win(Player::Black) => scores[j] += 2,
-                    GameState::Draw => {
-                        scores[i] += 1;
-                        scores[j] += 1;
-                    }
-                    GameState::Playing => (),
-                }
-                let mut wp = User::Bot(contenders[j].bot_clone());
-                let mut bp = User::Bot(contenders[i].bot_clone());
-                match game::run(&mut wp, &mut bp) {
-                    GameState::Win(crate::board::Player::White) => scores[j] += 2,
-                    GameState::Win(crate::board::Player::Black) => scores[i] += 2,
-                    GameState::Draw => {
-                        scores[i] += 1;
-                        scores[j] += 1;
-                    }
-                    GameState::Playing => (),
-                }
+    let checkpoint_path =
+        cli::take_flag(&mut args, "--checkpoint")?.ok_or("--checkpoint is required")?;
+    let resume = match cli::take_flag(&mut args, "--resume")? {
+        Some(path) => Some(read_to_string(path)?),
+        None => None,
+    };
+    // Older checkpoints are just bot strings
+    let checkpoint = resume
+        .as_deref()
+        .filter(|text| text.lines().any(|l| l.trim() == "[[bot]]"))
+        .map(str::parse::<Checkpoint>)
+        .transpose()?;
+    let kind = cli::take_parsed::<Kind>(&mut args, "--optimizer")?;
+    let bot1 = cli::take_flag(&mut args, "--bot1")?;
+    let bot2 = cli::take_flag(&mut args, "--bot2")?;
+    let depth = cli::take_parsed::<u8>(&mut args, "--depth")?;
+
+    let default = checkpoint
+        .as_ref()
+        .map(|c| c.config.clone())
+        .unwrap_or_default();
+    let mut config = GaConfig {
+        population: cli::take_parsed(&mut args, "--population")?.unwrap_or(default.population),
+        generations: cli::take_parsed(&mut args, "--generations")?.or(default.generations),
+        games: cli::take_parsed(&mut args, "--games")?.unwrap_or(default.games),
+        tournament_size: cli::take_parsed(&mut args, "--tournament")?
+            .unwrap_or(default.tournament_size),
+        elitism: cli::take_parsed(&mut args, "--elitism")?.unwrap_or(default.elitism),
+        crossover: cli::take_parsed(&mut args, "--crossover")?.unwrap_or(default.crossover),
+        crossover_rate: cli::take_parsed(&mut args, "--crossover-rate")?
+            .unwrap_or(default.crossover_rate),
+        mutation_rate: cli::take_parsed(&mut args, "--mutation-rate")?
+            .unwrap_or(default.mutation_rate),
+        mutation: cli::take_parsed(&mut args, "--mutation")?.unwrap_or(default.mutation),
+        mutation_strength: cli::take_parsed(&mut args, "--mutation-strength")?
+            .unwrap_or(default.mutation_strength),
+        section_steps: cli::take_parsed(&mut args, "--section-steps")?
+            .unwrap_or(default.section_steps),
+        hall_of_fame: cli::take_parsed(&mut args, "--hall-of-fame")?
+            .unwrap_or(default.hall_of_fame),
+        anchor_games: cli::take_parsed(&mut args, "--anchor-games")?
+            .unwrap_or(default.anchor_games),
+        adjudication: {
+            let given = cli::adjudication(&mut args)?;
+            Adjudication {
+                resign: given.resign.or(default.adjudication.resign),
+                draw: given.draw.or(default.adjudication.draw),
+                max_moves: given.max_moves.or(default.adjudication.max_moves),
+            }
+        },
+        concurrency: cli::concurrency(&mut args)?,
+        bot: bot1.clone().unwrap_or(default.bot),
+        benchmark_interval: cli::take_parsed(&mut args, "--benchmark-every")?
+            .unwrap_or(default.benchmark_interval),
+        benchmark_games: cli::take_parsed(&mut args, "--benchmark-games")?
+            .unwrap_or(default.benchmark_games),
+    };
+    let spsa_default = match checkpoint.as_ref().map(|c| &c.optimizer) {
+        Some(OptimizerState::Spsa { a, c, .. }) => SpsaConfig { a: *a, c: *c },
+        _ => SpsaConfig::default(),
+    };
+    let spsa = SpsaConfig {
+        a: cli::take_parsed(&mut args, "--spsa-a")?.unwrap_or(spsa_default.a),
+        c: cli::take_parsed(&mut args, "--spsa-c")?.unwrap_or(spsa_default.c),
+    };
+    let log = cli::take_flag(&mut args, "--log")?
+        .map(|path| Log::open(&path))
+        .transpose()?;
+    let mut rng = match &checkpoint {
+        Some(checkpoint) => {
+            if cli::take_flag(&mut args, "--seed")?.is_some() {
+                Err("--seed can't be combined with --resume, the checkpoint has its own")?;
             }
+            StdRng::seed_from_u64(checkpoint.seed)
         }
-        let max_index = scores
-            .iter()
-            .enumerate()
-            .fold((0_usize, 0_usize), |acc, x| {
-                if *x.1 > acc.1 {
-                    (x.0, *x.1)
-                } else {
-                    acc
-                }
-            });
-
-        let second_best = scores
-            .iter()
-            .enumerate()
-            .fold((0_usize, 0_usize), |acc, x| {
-                if *x.1 > acc.1 && x.0 != max_index.0 {
-                    (x.0, *x.1)
-                } else {
-                    acc
-                }
-            });
-
-        let best_b = contenders[max_index.0].bot_clone();
-        let second_b = contenders[second_best.0].bot_clone();
-        contenders[0] = best_b.bot_clone();
-        contenders[1] = second_b.bot_clone();
-        contenders[2] = best_b.modified();
-        contenders[3] = second_b.modified();
-
-        println!("writing to checkpoint");
-        println!(
-            "best bot bytes: {:?}",
-            best_b.evaluator.string_rep().as_bytes()
-        );
-        println!("best bot str: {:?}", best_b.evaluator.string_rep());
-        write(
-            &checkpoint_path,
-            best_b.evaluator.string_rep() + &second_b.evaluator.string_rep(),
-        )
-        .unwrap();
-        println!("wrote to checkpoint")
+        None => cli::seeded_rng(&mut args)?,
+    };
+    cli::finish(&args)?;
+    if resume.is_some() && (bot1.is_some() || bot2.is_some()) {
+        Err("--resume can't be combined with --bot1 or --bot2")?;
     }
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /*
-    Only for development, shows a stacktrace on stack overflows
-    (added since I accidentaly ifinitely called a recursive function.
-    Solved by adding a boolean flag to the get_pseudo_legal_king_moves method)
-    */
-    //unsafe { backtrace_on_stack_overflow::enable() };
-
-    let mut a = std::env::args();
-
-    // skip name of program
-    a.next();
-
-    let mut white_player = parse::parse(&mut a)?;
-
-    let mut black_player = parse::parse(&mut a)?;
-
-    #[cfg(feature = "gui")]
-    {
-        let mut app = game::Game {
-            white_player,
-            black_player,
-            ..Default::default()
-        };
-
-        return Ok(game::run(&mut app));
+    if let (Some(checkpoint), Some(kind)) = (&checkpoint, kind) {
+        if kind != checkpoint.optimizer.kind() {
+            Err("--optimizer must be the one of the checkpoint when resuming")?;
+        }
     }
+    let kind = kind
+        .or(checkpoint.as_ref().map(|c| c.optimizer.kind()))
+        .unwrap_or(Kind::Ga);
 
-    #[cfg(feature = "compare")]
-    {
-        let mut stdin = std::io::BufReader::new(std::io::stdin());
-
-        // get output file
-        println!("Ouput ? ");
-        let mut out_file_path = String::new();
-        stdin.read_line(&mut out_file_path).unwrap();
-        out_file_path = out_file_path.trim().to_string();
-        let out_file_path = PathBuf::from(out_file_path);
-
-        // Benchmark
-        let result = bench::run(&mut white_player, &mut black_player);
-
-        write(
-            out_file_path,
-            &format!(
-                "Bot 1 wins: {}\nBot 2 wins: {}\nDraws: {}",
-                result[0], result[1], result[2]
-            ),
-        )?;
-
-        return Ok(());
+    if config.population < 2 {
+        Err("--population must be at least 2")?;
     }
-
-    #[cfg(feature = "train")]
-    {
-        let mut stdin = std::io::BufReader::new(std::io::stdin());
-
-        println!("Load checkpoint? (type no to start from default)");
-        let mut checkpoint_path = String::new();
-        stdin.read_line(&mut checkpoint_path).unwrap();
-        checkpoint_path = checkpoint_path.trim().to_string();
-
-        if &checkpoint_path != "no" {
-            let mut checkpoint_file = File::open(&checkpoint_path)?;
-            let mut checkpoint_content = String::new();
-            checkpoint_file.read_to_string(&mut checkpoint_content);
-            checkpoint_content = checkpoint_content.trim().to_string();
-
-            let mut checkpoint_iter = checkpoint_content.split(" ").map(|x| x.to_owned());
-
-            white_player = parse(&mut checkpoint_iter)?;
-            black_player = parse(&mut checkpoint_iter)?;
-        } else {
-            white_player = User::Bot(Bot {
-                evaluator: Box::new(evaluator_0::Evaluator::default()),
-                search_depth: 4,
-                cache: FxHashMap::default(),
-            });
-            black_player = User::Bot(Bot {
-                evaluator: Box::new(evaluator_0::Evaluator::default()),
-                search_depth: 4,
-                cache: FxHashMap::default(),
-            });
+    for (p, flag) in [
+        (config.crossover_rate, "--crossover-rate"),
+        (config.mutation_rate, "--mutation-rate"),
+    ] {
+        if !(0.0..=1.0).contains(&p) {
+            Err(format!("{flag} must be between 0 and 1"))?;
         }
-
-        println!("Save to checkpoint?");
-        let mut checkpoint_path = String::new();
-        stdin.read_line(&mut checkpoint_path).unwrap();
-        checkpoint_path = checkpoint_path.trim().to_string();
-
-        let (mut b1, mut b2) = match (white_player, black_player) {
-            (User::Bot(b1), User::Bot(b2)) => (b1, b2),
-            _ => Err("Can't train with human bots")?,
-        };
-
-        train::train(b1, b2, checkpoint_path);
-
-        return Ok(());
     }
 
-    let mut board = Board::default();
-
-    let mut stdin = std::io::BufReader::new(std::io::stdin());
-    println!("\x1b[2J\x1b[H");
-    println!("{}", board);
-
-    loop {
-        match board.turn {
-            Player::White => match white_player {
-                User::Human => {
-                    let mut input = String::new();
-                    stdin.read_line(&mut input);
-                    if input.trim() == "u" {
-                        board.unmake_last();
-                    } else {
-                        let cmove: Result<Move, &str> = input.parse();
-
-                        if cmove.is_ok() {
-                            board.make_move(&cmove.clone().unwrap());
-                        }
-                    }
-                }
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut board);
-                    if let Some(m) = cmove {
-                        board.make_move(&m);
-                    }
-                }
-            },
-            Player::Black => match black_player {
-                User::Human => {
-                    let mut input = String::new();
-                    stdin.read_line(&mut input);
-                    if input.trim() == "u" {
-                        board.unmake_last();
-                    } else {
-                        let cmove: Result<Move, &str> = input.parse();
-
-                        if cmove.is_ok() {
-                            board.make_move(&cmove.clone().unwrap());
-                        }
-                    }
-                }
-                User::Bot(ref mut b) => {
-                    let cmove = b.find_best_move(&mut board);
-                    if let Some(m) = cmove {
-                        board.make_move(&m);
+    let (template, generation, mut optimizer): (_, _, Box<dyn Optimizer>) = match checkpoint {
+        Some(checkpoint) => {
+            let mut template = cli::bot(&config.bot)?;
+            template.search_depth = depth.unwrap_or(checkpoint.depth);
+            let generation = checkpoint.generation + 1;
+            let optimizer: Box<dyn Optimizer> = match kind {
+                Kind::Ga => Box::new(Ga::resume(&template, checkpoint, &config)?),
+                Kind::Spsa => Box::new(Spsa::resume(checkpoint, &config, &spsa)?),
+                Kind::Cmaes => Box::new(Cmaes::resume(&template, checkpoint, &config)?),
+            };
+            (template, generation, optimizer)
+        }
+        None => {
+            let mut seeds = match resume {
+                Some(text) => {
+                    // The first bot decides how all of them search
+                    if let Some(spec) = text.split_whitespace().next() {
+                        config.bot = spec.to_string();
                     }
+                    text.split_whitespace()
+                        .map(cli::bot)
+                        .collect::<Result<Vec<_>, _>>()?
                 }
-            },
-        }
-
-        println!("\x1b[2J\x1b[H");
-        println!("{}", board);
-
-        match board.get_game_state() {
-            GameState::Draw => {
-                #[cfg(not(feature = "compare"))]
-                println!("DRAW");
-                #[cfg(feature = "compare")]
-                println!("0");
-                break;
-            }
-            GameState::Win(Player::White) => {
-                #[cfg(not(feature = "compare"))]
-                println!("White Wins");
-                #[cfg(feature = "compare")]
-                println!("1");
-                break;
+                None => vec![
+                    cli::bot(bot1.as_deref().unwrap_or("DEFAULT"))?,
+                    cli::bot(bot2.as_deref().unwrap_or("DEFAULT"))?,
+                ],
+            };
+            if seeds.is_empty() {
+                Err("empty checkpoint")?;
             }
-            GameState::Win(Player::Black) => {
-                #[cfg(not(feature = "compare"))]
-                println!("Black Wins");
-                #[cfg(feature = "compare")]
-                println!("-1");
-                break;
+            if let Some(depth) = depth {
+                seeds.iter_mut().for_each(|b| b.search_depth = depth);
             }
-            _ => (),
+
+            let genomes = seeds
+                .iter()
+                .map(ga::genome)
+                .collect::<Result<Vec<_>, _>>()?;
+            let template = seeds.swap_remove(0);
+            // SPSA and CMA-ES improve a single bot, the first one
+            let optimizer: Box<dyn Optimizer> = match kind {
+                Kind::Ga => {
+                    let population = ga::initial_population(&genomes, &config, &mut rng);
+                    Box::new(Ga::new(&template, population, &config)?)
+                }
+                Kind::Spsa => Box::new(Spsa::new(&genomes[0], &config, &spsa)),
+                Kind::Cmaes => Box::new(Cmaes::new(&template, &genomes[0], &config)?),
+            };
+            (template, 0, optimizer)
         }
-    }
-    Ok(())
+    };
+
+    optimizer::run(
+        &template,
+        optimizer.as_mut(),
+        generation,
+        &config,
+        &checkpoint_path,
+        log,
+        &mut rng,
+    )
 }
@@ -0,0 +1,89 @@
+//! Statistics of every generation, printed and written to a CSV log
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+};
+
+use chlang::stats::MatchScore;
+
+pub struct GenerationStats {
+    pub generation: usize,
+    pub best: f64,
+    pub mean: f64,
+    pub worst: f64,
+    pub draw_rate: f64,
+    /// Average number of plies per game, a move of either side is one ply
+    pub game_plies: f64,
+    /// Result of the best bot against the default evaluator, from the best
+    /// bot's perspective, in generations where it was benchmarked
+    pub baseline: Option<MatchScore>,
+    /// Time the generation took, including the benchmark
+    pub seconds: f64,
+}
+
+impl GenerationStats {
+    pub fn print(&self) {
+        println!(
+            "generation {}: best {:.3} mean {:.3} worst {:.3} draws {:.1}% plies {:.1} ({:.1}s)",
+            self.generation,
+            self.best,
+            self.mean,
+            self.worst,
+            self.draw_rate * 100.0,
+            self.game_plies,
+            self.seconds
+        );
+        if let Some(baseline) = self.baseline {
+            println!(
+                "best against default: {} wins, {} losses, {} draws\n{baseline}",
+                baseline.wins, baseline.losses, baseline.draws
+            );
+        }
+    }
+}
+
+const HEADER: &str =
+    "generation,best,mean,worst,draw_rate,game_plies,baseline_score,baseline_elo,seconds";
+
+/// CSV file with one line per generation. The baseline columns are empty in
+/// generations without a benchmark.
+pub struct Log {
+    file: File,
+}
+
+impl Log {
+    /// New lines are appended, so that a resumed run continues its log
+    pub fn open(path: &str) -> io::Result<Log> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{HEADER}")?;
+        }
+        Ok(Log { file })
+    }
+
+    pub fn write(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        let (score, elo) = match stats.baseline {
+            // Without games the score would be NaN
+            Some(baseline) if baseline.games() > 0 => (
+                format!("{:.3}", baseline.score()),
+                baseline
+                    .elo()
+                    .map(|(elo, _)| format!("{elo:.1}"))
+                    .unwrap_or_default(),
+            ),
+            _ => (String::new(), String::new()),
+        };
+        writeln!(
+            self.file,
+            "{},{:.4},{:.4},{:.4},{:.4},{:.1},{score},{elo},{:.1}",
+            stats.generation,
+            stats.best,
+            stats.mean,
+            stats.worst,
+            stats.draw_rate,
+            stats.game_plies,
+            stats.seconds
+        )
+    }
+}
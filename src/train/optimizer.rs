@@ -0,0 +1,103 @@
+//! Ways of improving the bytes of `evaluator_0` bots by playing games, and the
+//! training loop they share
+
+use std::{error::Error, fmt::Display, fs::write, str::FromStr, time::Instant};
+
+use chlang::tree_evaluator::Bot;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::ga::{benchmark, bot, GaConfig, GameTotals, Genome};
+use super::metrics::{GenerationStats, Log};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Ga,
+    Spsa,
+    Cmaes,
+}
+
+impl FromStr for Kind {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ga" => Ok(Kind::Ga),
+            "spsa" => Ok(Kind::Spsa),
+            "cmaes" => Ok(Kind::Cmaes),
+            _ => Err("optimizer must be ga, spsa or cmaes"),
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Ga => write!(f, "ga"),
+            Kind::Spsa => write!(f, "spsa"),
+            Kind::Cmaes => write!(f, "cmaes"),
+        }
+    }
+}
+
+pub trait Optimizer {
+    /// Play the games of one generation and learn from them. Returns the
+    /// fitness of every bot that played, in points per game.
+    fn step(&mut self, template: &Bot, rng: &mut StdRng) -> (Vec<f64>, GameTotals);
+
+    /// The bytes that are currently thought to be the best
+    fn best(&self) -> Genome;
+
+    /// Contents of the checkpoint file after `generation`. The random number
+    /// generator is reseeded with `seed` afterwards.
+    fn checkpoint(&self, generation: usize, seed: u64, template: &Bot) -> String;
+}
+
+/// Run `optimizer` from `generation` on. Statistics are printed and logged
+/// after every generation, and a checkpoint is written to `checkpoint_path`.
+/// The random number generator is reseeded from the seed stored in the
+/// checkpoint, so that resuming plays the same games.
+pub fn run(
+    template: &Bot,
+    optimizer: &mut dyn Optimizer,
+    mut generation: usize,
+    config: &GaConfig,
+    checkpoint_path: &str,
+    mut log: Option<Log>,
+    rng: &mut StdRng,
+) -> Result<(), Box<dyn Error>> {
+    while config.generations.is_none_or(|g| generation < g) {
+        let start = Instant::now();
+        let (fitness, totals) = optimizer.step(template, rng);
+
+        let best = optimizer.best();
+        let benchmarked = config.benchmark_interval > 0
+            && config.benchmark_games > 0
+            && (generation + 1).is_multiple_of(config.benchmark_interval);
+        let stats = GenerationStats {
+            generation,
+            best: fitness.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean: fitness.iter().sum::<f64>() / fitness.len() as f64,
+            worst: fitness.iter().copied().fold(f64::INFINITY, f64::min),
+            draw_rate: totals.draws as f64 / totals.games.max(1) as f64,
+            game_plies: totals.plies as f64 / totals.games.max(1) as f64,
+            baseline: benchmarked.then(|| benchmark(template, &best, config, rng)),
+            seconds: start.elapsed().as_secs_f64(),
+        };
+        stats.print();
+        println!(
+            "best bot str: {}",
+            bot(template, &best).evaluator.string_rep()
+        );
+        if let Some(log) = &mut log {
+            log.write(&stats)?;
+        }
+
+        let seed = rng.gen();
+        write(
+            checkpoint_path,
+            optimizer.checkpoint(generation, seed, template),
+        )?;
+        *rng = StdRng::seed_from_u64(seed);
+        generation += 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Simultaneous perturbation stochastic approximation. Every generation all
+//! values are moved by the same amount in random directions, and a bot with
+//! these values plays a bot with the values moved the opposite way. The match
+//! result estimates the gradient, and the values follow it.
+
+use std::error::Error;
+
+use chlang::tree_evaluator::Bot;
+use rand::{rngs::StdRng, Rng};
+
+use super::checkpoint::{Checkpoint, OptimizerState};
+use super::ga::{evaluate, rounded, GaConfig, GameTotals, Genome, Individual, MAX_VALUE};
+use super::optimizer::Optimizer;
+
+#[derive(Debug, Clone)]
+pub struct SpsaConfig {
+    /// Size of the steps towards the winner
+    pub a: f64,
+    /// Size of the perturbations
+    pub c: f64,
+}
+
+impl Default for SpsaConfig {
+    fn default() -> Self {
+        Self { a: 100.0, c: 4.0 }
+    }
+}
+
+pub struct Spsa {
+    config: GaConfig,
+    spsa: SpsaConfig,
+    /// The values without rounding, so that small steps add up
+    theta: Vec<f64>,
+    iteration: usize,
+    /// The pair of the last generation with its fitness, best first
+    ranked: Vec<(Individual, f64)>,
+}
+
+impl Spsa {
+    pub fn new(start: &Genome, config: &GaConfig, spsa: &SpsaConfig) -> Spsa {
+        Spsa {
+            config: config.clone(),
+            spsa: spsa.clone(),
+            theta: start.iter().map(|b| *b as f64).collect(),
+            iteration: 0,
+            ranked: vec![],
+        }
+    }
+
+    /// Continue with the values and iteration of a checkpoint
+    pub fn resume(
+        checkpoint: Checkpoint,
+        config: &GaConfig,
+        spsa: &SpsaConfig,
+    ) -> Result<Spsa, Box<dyn Error>> {
+        let OptimizerState::Spsa {
+            theta, iteration, ..
+        } = checkpoint.optimizer
+        else {
+            Err("not an SPSA checkpoint")?
+        };
+        Ok(Spsa {
+            config: config.clone(),
+            spsa: spsa.clone(),
+            theta,
+            iteration,
+            ranked: checkpoint.population,
+        })
+    }
+}
+
+impl Optimizer for Spsa {
+    fn step(&mut self, template: &Bot, rng: &mut StdRng) -> (Vec<f64>, GameTotals) {
+        // The usual decay of the step sizes, with a tenth of the run as the
+        // stability constant
+        let k = self.iteration as f64;
+        let stability = self.config.generations.map_or(10.0, |g| g as f64 / 10.0);
+        let a = self.spsa.a / (k + 1.0 + stability).powf(0.602);
+        let c = self.spsa.c / (k + 1.0).powf(0.101);
+
+        let delta = (0..self.theta.len())
+            .map(|_| if rng.gen_bool(0.5) { 1.0 } else { -1.0 })
+            .collect::<Vec<f64>>();
+        let moved = |sign: f64| {
+            let values = self
+                .theta
+                .iter()
+                .zip(&delta)
+                .map(|(t, d)| t + sign * c * d)
+                .collect::<Vec<_>>();
+            Individual {
+                genome: rounded(&values),
+                sigma: c,
+            }
+        };
+        let pair = [moved(1.0), moved(-1.0)];
+
+        // With two bots every game is between them
+        let (fitness, totals) = evaluate(template, &pair, &[], &self.config, rng);
+        let difference = fitness[0] - fitness[1];
+        for (t, d) in self.theta.iter_mut().zip(&delta) {
+            *t = (*t + a * difference / (2.0 * c * d)).clamp(0.0, MAX_VALUE as f64);
+        }
+        self.iteration += 1;
+
+        let mut ranked = pair.into_iter().zip(fitness.clone()).collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        self.ranked = ranked;
+        (fitness, totals)
+    }
+
+    fn best(&self) -> Genome {
+        rounded(&self.theta)
+    }
+
+    fn checkpoint(&self, generation: usize, seed: u64, template: &Bot) -> String {
+        Checkpoint {
+            generation,
+            seed,
+            depth: template.search_depth,
+            config: self.config.clone(),
+            population: self.ranked.clone(),
+            hall_of_fame: vec![],
+            optimizer: OptimizerState::Spsa {
+                a: self.spsa.a,
+                c: self.spsa.c,
+                theta: self.theta.clone(),
+                iteration: self.iteration,
+            },
+        }
+        .to_string()
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rustc_hash::FxHasher;
+
+use crate::{board::Board, cmove::Move};
+
+/// Number of entries in a table created with [`TranspositionTable::default`] (4 MB)
+pub const DEFAULT_ENTRIES: usize = 1 << 18;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    /// The real value is at least this (the node failed high)
+    Lower,
+    /// The real value is at most this (the node failed low)
+    Upper,
+}
+
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    pub value: isize,
+    pub bound: Bound,
+    pub depth: u8,
+    pub best_move: Option<Move>,
+}
+
+impl CacheEntry {
+    // | value: 32 | depth: 8 | bound: 2 | move: 16 |
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let cmove = self.best_move.map(|m| m.bits()).unwrap_or(0);
+        (self.value as i32 as u32 as u64) << 32
+            | (self.depth as u64) << 24
+            | bound << 16
+            | cmove as u64
+    }
+
+    fn unpack(data: u64) -> Self {
+        let bound = match (data >> 16) & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let cmove = (data & 0xffff) as u16;
+        Self {
+            value: (data >> 32) as u32 as i32 as isize,
+            bound,
+            depth: (data >> 24) as u8,
+            best_move: (cmove != 0).then(|| Move::from_bits(cmove)),
+        }
+    }
+}
+
+/// Fixed size hash table of search results that can be shared between
+/// search threads without locking. Every slot is two words, the key xor'ed
+/// with the data and the data itself, so a slot torn by two threads writing
+/// at once no longer matches its key and is treated as empty.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    /// Create a table with room for `entries` entries (rounded up to a power of two)
+    pub fn new(entries: usize) -> Self {
+        let entries = entries.max(1).next_power_of_two();
+        Self {
+            slots: (0..entries)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    pub fn hash(board: &Board) -> u64 {
+        let mut hasher = FxHasher::default();
+        board.key().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn slot(&self, hash: u64) -> &[AtomicU64; 2] {
+        &self.slots[hash as usize & (self.slots.len() - 1)]
+    }
+
+    pub fn get(&self, hash: u64) -> Option<CacheEntry> {
+        let [key, data] = self.slot(hash);
+        let data = data.load(Ordering::Relaxed);
+        let key = key.load(Ordering::Relaxed);
+        if data != 0 && key ^ data == hash {
+            Some(CacheEntry::unpack(data))
+        } else {
+            None
+        }
+    }
+
+    /// Store an entry. An entry for the same position is only replaced by a
+    /// deeper or exact result, entries for other positions always are.
+    pub fn insert(&self, hash: u64, entry: CacheEntry) {
+        let [key, data] = self.slot(hash);
+        if let Some(old) = self.get(hash) {
+            if old.depth > entry.depth && entry.bound != Bound::Exact {
+                return;
+            }
+        }
+        let new_data = entry.pack();
+        key.store(hash ^ new_data, Ordering::Relaxed);
+        data.store(new_data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for [key, data] in self.slots.iter() {
+            key.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_ENTRIES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_round_trip() {
+        let moves = [
+            None,
+            Some("e2e4".parse().unwrap()),
+            Some("a7a8Q".parse().unwrap()),
+        ];
+        for value in [0, 1, -1, 35, -35, isize::from(i16::MAX), -1_000_000] {
+            for bound in [Bound::Exact, Bound::Lower, Bound::Upper] {
+                for depth in [0, 1, 7, u8::MAX] {
+                    for best_move in moves {
+                        let entry = CacheEntry {
+                            value,
+                            bound,
+                            depth,
+                            best_move,
+                        };
+                        let unpacked = CacheEntry::unpack(entry.pack());
+                        assert_eq!(unpacked.value, value);
+                        assert_eq!(unpacked.bound, bound);
+                        assert_eq!(unpacked.depth, depth);
+                        assert_eq!(unpacked.best_move, best_move);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,193 +1,1095 @@
-use std::{borrow::Borrow, collections::HashMap};
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 use rand::prelude::*;
-use rustc_hash::FxHashMap;
 
 use crate::{
-    board::{Board, GameState, KeyStruct, Player},
+    board::{Board, GameState, Player},
+    book::OpeningBook,
     cmove::Move,
+    transposition::{Bound, CacheEntry, TranspositionTable},
 };
 
+/// Score of a checkmate at the root. Mates further away score lower
+/// (MATE_SCORE - plies to mate) so that the shortest mate is preferred.
+pub const MATE_SCORE: isize = 1_000_000;
+const INFINITY: isize = MATE_SCORE + 1;
+/// Any score above this is a forced mate
+const MATE_THRESHOLD: isize = MATE_SCORE - 1_000;
+/// Half width of the initial aspiration window around the previous iteration's score
+const ASPIRATION_WINDOW: isize = 25;
+/// Extensions stop once the search is this many times deeper than asked for
+const MAX_EXTENSION_FACTOR: usize = 2;
+/// Depth searched to when only a node or time limit is given
+pub const MAX_DEPTH: u8 = 64;
+
 pub trait Eval {
+    /// Evaluate the board from white's perspective
     fn evaluate(&self, board: &mut Board) -> isize;
-    fn modified(&self) -> Box<dyn Eval + Sync + Send>;
+    fn modified(&self, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send>;
+    /// A child of this evaluator and `other`. Parents of different kinds
+    /// give a copy of `self`.
+    fn crossover(&self, other: &dyn Eval, rng: &mut StdRng) -> Box<dyn Eval + Sync + Send>;
     fn bot_clone(&self) -> Box<dyn Eval + Sync + Send>;
     fn string_rep(&self) -> String;
+
+    /// Evaluate the board from the perspective of the side to move
+    fn evaluate_relative(&self, board: &mut Board) -> isize {
+        match board.turn {
+            Player::White => self.evaluate(board),
+            Player::Black => -self.evaluate(board),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Score from the perspective of the side to move
+    pub score: isize,
+    pub depth: u8,
+    /// Principal variation, starting with the best move
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    /// Rank of this line among the analysed root moves, 1 is the best
+    pub multipv: usize,
+}
+
+impl SearchResult {
+    /// Number of moves until mate if the score is a forced mate.
+    /// Positive if the side to move mates, negative if it gets mated.
+    pub fn mate_in(&self) -> Option<isize> {
+        if self.score > MATE_THRESHOLD {
+            Some((MATE_SCORE - self.score + 1) / 2)
+        } else if self.score < -MATE_THRESHOLD {
+            Some(-(MATE_SCORE + self.score) / 2)
+        } else {
+            None
+        }
+    }
 }
 
-pub fn eval(
-    board: &mut Board,
-    depth: u8,
-    evaluator: &Box<dyn Eval + Sync + Send>,
-    mut alpha: isize,
-    mut beta: isize,
-    cache: &mut FxHashMap<KeyStruct, (isize, bool, u8)>,
-) -> isize {
-    match board.get_game_state() {
-        GameState::Draw => return 0,
-        GameState::Playing => (),
-        GameState::Win(Player::White) => return isize::MAX,
-        GameState::Win(Player::Black) => return isize::MIN,
+/// When to stop searching. Without any limits the bot searches to its
+/// `search_depth`, with only a node or time limit it searches until that is hit.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    /// Time left on the clock, a share of it is used when there is no `movetime`
+    pub time: Option<Duration>,
+    /// Time added to the clock after every move
+    pub increment: Duration,
+    /// Moves until more time is added, the time has to last for the whole
+    /// game if not set
+    pub moves_to_go: Option<u32>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u8) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
     }
-    if let Some((value, fin, max_d)) = cache.get(&board.key()) {
-        if depth <= *max_d {
-            if *fin {
-                return *value;
-            } else if *value > beta && board.turn == Player::White {
-                return *value;
-            } else if *value < alpha && board.turn == Player::Black {
-                return *value;
+
+    /// Time this search may take. From the clock it is an even share of the
+    /// time for the moves to go, assuming 30 if unknown, and half the
+    /// increment, but never more than 90% of the time left.
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.movetime.or(self.time.map(|time| {
+            let moves = self.moves_to_go.unwrap_or(30).max(1);
+            (time / moves + self.increment / 2).min(time.mul_f64(0.9))
+        }))
+    }
+}
+
+type DepthCallback = Arc<dyn Fn(&SearchResult) + Send + Sync>;
+
+/// Lets another thread stop a running search and follow its progress.
+/// Clones share the same stop flag.
+#[derive(Clone, Default)]
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    on_depth: Option<DepthCallback>,
+}
+
+impl SearchHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `callback` with the current result every time a depth is completed
+    pub fn with_callback<F>(callback: F) -> Self
+    where
+        F: Fn(&SearchResult) + Send + Sync + 'static,
+    {
+        Self {
+            stop: Arc::new(AtomicBool::new(false)),
+            on_depth: Some(Arc::new(callback)),
+        }
+    }
+
+    /// Ask the search to return as soon as possible
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Clear the stop flag so the handle can be used for another search
+    pub fn reset(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Selectivity of the search. Everything is off by default so that
+/// each technique can be compared against plain alpha-beta.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Skip a turn and prune if the position is still too good for the opponent
+    pub null_move_pruning: bool,
+    /// Search quiet moves late in the move ordering to a lower depth
+    pub late_move_reductions: bool,
+    /// Search one ply deeper after moves that give check
+    pub check_extensions: bool,
+}
+
+impl FromStr for SearchOptions {
+    type Err = &'static str;
+    /// Comma separated list of `nmp`, `lmr` and `ext`, or `all` / `none`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = SearchOptions::default();
+        for option in s.split(',').map(|x| x.trim()) {
+            match option {
+                "nmp" => options.null_move_pruning = true,
+                "lmr" => options.late_move_reductions = true,
+                "ext" => options.check_extensions = true,
+                "all" => {
+                    options.null_move_pruning = true;
+                    options.late_move_reductions = true;
+                    options.check_extensions = true;
+                }
+                "none" | "" => (),
+                _ => return Err("unknown search option (expected nmp, lmr, ext, all or none)"),
             }
         }
+        Ok(options)
     }
+}
 
-    if depth == 0 {
-        let val = evaluator.evaluate(board);
-        let e = cache.entry(board.key()).or_insert((0, true, depth));
+impl Display for SearchOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names = Vec::new();
+        if self.null_move_pruning {
+            names.push("nmp");
+        }
+        if self.late_move_reductions {
+            names.push("lmr");
+        }
+        if self.check_extensions {
+            names.push("ext");
+        }
+        if names.is_empty() {
+            names.push("none");
+        }
+        write!(f, "{}", names.join(","))
+    }
+}
 
-        e.0 = val;
-        return val;
+// Mate scores are stored relative to the node instead of the root
+// so that they stay correct when the position is reached at another ply
+fn value_to_cache(value: isize, ply: usize) -> isize {
+    if value > MATE_THRESHOLD {
+        value + ply as isize
+    } else if value < -MATE_THRESHOLD {
+        value - ply as isize
+    } else {
+        value
     }
+}
 
-    let mut pseudo_legal_moves = board.get_pseudo_legal_moves();
+fn value_from_cache(value: isize, ply: usize) -> isize {
+    if value > MATE_THRESHOLD {
+        value - ply as isize
+    } else if value < -MATE_THRESHOLD {
+        value + ply as isize
+    } else {
+        value
+    }
+}
 
-    pseudo_legal_moves.sort_unstable_by_key(|cmove| {
+/// Captures first, then quiet moves
+fn order_moves(board: &Board, moves: &mut [Move]) {
+    moves.sort_by_key(|cmove| {
         if board.piece_type(&cmove.to()).is_some() {
             -100
         } else {
             0
         }
     });
+}
 
-    match board.turn {
-        Player::White => {
-            let mut best = isize::MIN;
-            for cmove in pseudo_legal_moves.into_iter() {
-                if board.make_move(&cmove).is_ok() {
-                    best = best.max(eval(board, depth - 1, evaluator, alpha, beta, cache));
-                    if best >= beta {
-                        board.unmake_last();
-                        break;
+/// Put the move that was best last time this node was searched first
+fn move_to_front(moves: &mut [Move], cmove: &Move) {
+    if let Some(i) = moves.iter().position(|m| m == cmove) {
+        moves[..=i].rotate_right(1);
+    }
+}
+
+/// What a single search thread should do
+struct SearchThread<'a> {
+    stop: &'a AtomicBool,
+    id: usize,
+    /// Progress is only reported from the main thread
+    handle: Option<&'a SearchHandle>,
+    max_depth: u8,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    multipv: usize,
+    /// Seed for the order of equal root moves
+    seed: u64,
+}
+
+struct Searcher<'a> {
+    evaluator: &'a (dyn Eval + Sync + Send),
+    cache: &'a TranspositionTable,
+    /// Set from outside when the search should be abandoned
+    stop: &'a AtomicBool,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    /// Set once the search has been abandoned, results are then meaningless
+    stopped: bool,
+    options: &'a SearchOptions,
+    max_ply: usize,
+    /// Set while searching the reply to a null move
+    after_null_move: bool,
+    nodes: u64,
+}
+
+impl Searcher<'_> {
+    /// Negamax alpha-beta with principal variation search.
+    /// Returns the score from the perspective of the side to move and
+    /// fills `pv` with the best line found from this node.
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: u8,
+        mut alpha: isize,
+        beta: isize,
+        ply: usize,
+        pv: &mut Vec<Move>,
+    ) -> isize {
+        self.nodes += 1;
+        pv.clear();
+        let after_null_move = std::mem::take(&mut self.after_null_move);
+        if self.should_stop() {
+            return 0;
+        }
+
+        match board.get_game_state() {
+            GameState::Draw => return 0,
+            // The side to move can't have won, so it is checkmated
+            GameState::Win(_) => return -(MATE_SCORE - ply as isize),
+            GameState::Playing => (),
+        }
+
+        if depth == 0 {
+            return self.evaluator.evaluate_relative(board);
+        }
+
+        let hash = TranspositionTable::hash(board);
+        let mut cache_move = None;
+        if let Some(entry) = self.cache.get(hash) {
+            cache_move = entry.best_move;
+            if entry.depth >= depth {
+                let value = value_from_cache(entry.value, ply);
+                match entry.bound {
+                    Bound::Exact => {
+                        // The table is only keyed by the hash, so the move
+                        // could come from another position
+                        if let Some(m) = entry.best_move {
+                            if board.make_move(&m).is_ok() {
+                                board.unmake_last();
+                                pv.push(m);
+                            }
+                        }
+                        return value;
                     }
-                    alpha = alpha.max(best);
-                    board.unmake_last();
+                    Bound::Lower if value >= beta => return value,
+                    Bound::Upper if value <= alpha => return value,
+                    _ => (),
                 }
             }
-            let x = cache.entry(board.key()).or_insert((0, true, depth));
-            x.2 = x.2.max(depth);
-            x.1 = true;
-            x.0 = best;
-            return best;
-        }
-        Player::Black => {
-            let mut best = isize::MAX;
-            for cmove in pseudo_legal_moves.into_iter() {
-                if board.make_move(&cmove).is_ok() {
-                    best = best.min(eval(board, depth - 1, evaluator, alpha, beta, cache));
-                    if best <= alpha {
-                        board.unmake_last();
-                        break;
-                    }
-                    beta = beta.min(best);
-                    board.unmake_last();
-                }
+        }
+
+        let in_check = board.in_check();
+
+        // Null move pruning. Not done in pawn endgames where zugzwang is
+        // common and passing could be better than every real move,
+        // and not twice in a row.
+        if self.options.null_move_pruning
+            && depth >= 3
+            && !in_check
+            && beta.abs() < MATE_THRESHOLD
+            && board.has_non_pawn_material(&board.turn)
+            && !after_null_move
+        {
+            let reduction = if depth >= 6 { 3 } else { 2 };
+            let mut null_pv = Vec::new();
+            board.make_null_move();
+            self.after_null_move = true;
+            let score = -self.negamax(
+                board,
+                depth - 1 - reduction,
+                -beta,
+                -beta + 1,
+                ply + 1,
+                &mut null_pv,
+            );
+            board.unmake_last();
+            if score >= beta {
+                return beta;
+            }
+        }
+
+        let mut moves = board.get_pseudo_legal_moves();
+        order_moves(board, &mut moves);
+        if let Some(ref m) = cache_move {
+            move_to_front(&mut moves, m);
+        }
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut searched = 0;
+        let mut child_pv = Vec::new();
+
+        for cmove in moves {
+            let quiet =
+                board.piece_type(&cmove.to()).is_none() && cmove.promotion_bitboard_index() == 0;
+            if board.make_move(&cmove).is_err() {
+                continue;
+            }
+            let score = self.search_move(
+                board,
+                depth,
+                alpha,
+                beta,
+                ply,
+                searched,
+                quiet && !in_check,
+                &mut child_pv,
+            );
+            board.unmake_last();
+            searched += 1;
+
+            if score > best {
+                best = score;
+                best_move = Some(cmove);
+                pv.clear();
+                pv.push(cmove);
+                pv.extend_from_slice(&child_pv);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        if !self.stopped {
+            self.cache.insert(
+                hash,
+                CacheEntry {
+                    value: value_to_cache(best, ply),
+                    bound,
+                    depth,
+                    best_move,
+                },
+            );
+        }
+        best
+    }
+
+    /// Search the position after a move was made at `ply` with `depth` left.
+    /// The first move gets the full window, later moves a null window
+    /// (possibly at a reduced depth) that is widened only if the move turns
+    /// out to be better than the current best.
+    #[allow(clippy::too_many_arguments)]
+    fn search_move(
+        &mut self,
+        board: &mut Board,
+        depth: u8,
+        alpha: isize,
+        beta: isize,
+        ply: usize,
+        searched: usize,
+        reducible: bool,
+        pv: &mut Vec<Move>,
+    ) -> isize {
+        let gives_check = board.in_check();
+        let mut new_depth = depth - 1;
+        if self.options.check_extensions && gives_check && ply < self.max_ply {
+            new_depth += 1;
+        }
+
+        if searched == 0 {
+            return -self.negamax(board, new_depth, -beta, -alpha, ply + 1, pv);
+        }
+
+        let reduction = if self.options.late_move_reductions
+            && reducible
+            && !gives_check
+            && depth >= 3
+            && searched >= 3
+        {
+            if searched >= 6 {
+                2
+            } else {
+                1
+            }
+        } else {
+            0
+        };
+
+        let mut score = -self.negamax(
+            board,
+            new_depth.saturating_sub(reduction),
+            -alpha - 1,
+            -alpha,
+            ply + 1,
+            pv,
+        );
+        if reduction > 0 && score > alpha {
+            score = -self.negamax(board, new_depth, -alpha - 1, -alpha, ply + 1, pv);
+        }
+        if score > alpha && score < beta {
+            score = -self.negamax(board, new_depth, -beta, -alpha, ply + 1, pv);
+        }
+        score
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped {
+            self.stopped = self.stop.load(Ordering::Relaxed)
+                || self.node_limit.is_some_and(|limit| self.nodes > limit)
+                // Reading the clock is slow compared to a node, so only do it now and then.
+                // Nodes are slow enough that checking more rarely overshoots short
+                // time limits by tens of milliseconds.
+                || (self.nodes & 63 == 0 && self.deadline.is_some_and(|d| Instant::now() >= d));
+        }
+        self.stopped
+    }
+
+    /// Search all root moves in the window, moving the best one to the front
+    fn search_root(
+        &mut self,
+        board: &mut Board,
+        moves: &mut [Move],
+        depth: u8,
+        mut alpha: isize,
+        beta: isize,
+        pv: &mut Vec<Move>,
+    ) -> isize {
+        let mut best = -INFINITY;
+        let mut best_index = None;
+        let mut searched = 0;
+        let mut child_pv = Vec::new();
+
+        for (i, &cmove) in moves.iter().enumerate() {
+            if board.make_move(&cmove).is_err() {
+                continue;
+            }
+            // Root moves are shuffled, so a late one is not a bad one
+            let score =
+                self.search_move(board, depth, alpha, beta, 0, searched, false, &mut child_pv);
+            board.unmake_last();
+            searched += 1;
+
+            if score > best {
+                best = score;
+                best_index = Some(i);
+                pv.clear();
+                pv.push(cmove);
+                pv.extend_from_slice(&child_pv);
             }
-            let x = cache.entry(board.key()).or_insert((0, true, depth));
-            x.2 = x.2.max(depth);
-            x.1 = true;
-            x.0 = best;
-            return best;
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if let Some(i) = best_index {
+            moves[..=i].rotate_right(1);
         }
+        best
     }
 }
 
 pub struct Bot {
     pub evaluator: Box<dyn Eval + Sync + Send>,
     pub search_depth: u8,
-    pub cache: FxHashMap<KeyStruct, (isize, bool, u8)>,
+    /// Shared with clones of this bot and with its helper threads
+    pub cache: Arc<TranspositionTable>,
+    pub options: SearchOptions,
+    /// Number of threads searching at once (Lazy SMP). Helper threads only
+    /// fill the shared cache, the move is always the main thread's.
+    pub threads: usize,
+    /// Source of all randomness in the search, seed it with `new_game`
+    /// to replay the same games. Searches with more than one thread are
+    /// not reproducible.
+    pub rng: StdRng,
+    /// Book moves are played instead of searching while there are any
+    pub book: Option<Arc<OpeningBook>>,
 }
+
+/// Unlike `bot_clone`, a clone shares the transposition table, so that it can
+/// search in another thread and still use what this bot found out
 impl Clone for Bot {
     fn clone(&self) -> Self {
         Self {
             evaluator: self.evaluator.bot_clone(),
             search_depth: self.search_depth,
             cache: self.cache.clone(),
+            options: self.options.clone(),
+            threads: self.threads,
+            rng: self.rng.clone(),
+            book: self.book.clone(),
         }
     }
 }
 
 impl Bot {
+    pub fn new(evaluator: Box<dyn Eval + Sync + Send>, search_depth: u8) -> Self {
+        Self {
+            evaluator,
+            search_depth,
+            cache: Arc::new(TranspositionTable::default()),
+            options: SearchOptions::default(),
+            threads: 1,
+            rng: StdRng::from_entropy(),
+            book: None,
+        }
+    }
+
+    /// Forget everything from earlier games and reseed the random number
+    /// generator, so that the same seed always plays the same moves
+    pub fn new_game(&mut self, seed: u64) {
+        self.cache.clear();
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
     pub fn find_best_move(&mut self, board: &mut Board) -> Option<Move> {
-        let mut pseudo_legal_moves = board.get_pseudo_legal_moves();
+        self.search(board).best_move
+    }
 
-        let mut rng = rand::thread_rng();
-        pseudo_legal_moves.shuffle(&mut rng);
+    pub fn search(&mut self, board: &mut Board) -> SearchResult {
+        self.search_with(board, &SearchHandle::default())
+    }
 
-        pseudo_legal_moves.sort_unstable_by_key(|cmove| {
-            if board.piece_type(&cmove.to()).is_some() {
-                -100
+    /// Stopping the search through the handle returns the result of the
+    /// last completed depth. Book moves are returned with depth 0.
+    pub fn search_with(&mut self, board: &mut Board, handle: &SearchHandle) -> SearchResult {
+        self.search_within(board, &SearchLimits::default(), handle)
+    }
+
+    /// Like `search_with`, but stopping at `limits` instead of the bot's depth
+    pub fn search_within(
+        &mut self,
+        board: &mut Board,
+        limits: &SearchLimits,
+        handle: &SearchHandle,
+    ) -> SearchResult {
+        if let Some(book_move) = self
+            .book
+            .as_ref()
+            .and_then(|b| b.pick(board, &mut self.rng))
+        {
+            return SearchResult {
+                best_move: Some(book_move),
+                score: 0,
+                depth: 0,
+                pv: vec![book_move],
+                nodes: 0,
+                multipv: 1,
+            };
+        }
+        self.analyse_with(board, limits, 1, handle).swap_remove(0)
+    }
+
+    /// The `multipv` best root moves with their scores and lines, best first
+    pub fn analyse(
+        &mut self,
+        board: &mut Board,
+        limits: &SearchLimits,
+        multipv: usize,
+    ) -> Vec<SearchResult> {
+        self.analyse_with(board, limits, multipv, &SearchHandle::default())
+    }
+
+    /// Search with `threads` threads. The helpers search the same position
+    /// with a different move order and starting depth, and are stopped as
+    /// soon as the main thread is done.
+    pub fn analyse_with(
+        &mut self,
+        board: &mut Board,
+        limits: &SearchLimits,
+        multipv: usize,
+        handle: &SearchHandle,
+    ) -> Vec<SearchResult> {
+        let budget = limits.time_budget();
+        let max_depth = limits
+            .depth
+            .unwrap_or(if limits.nodes.is_some() || budget.is_some() {
+                MAX_DEPTH
             } else {
-                0
+                self.search_depth
+            });
+        let main = SearchThread {
+            stop: &handle.stop,
+            id: 0,
+            handle: Some(handle),
+            max_depth,
+            node_limit: limits.nodes,
+            deadline: budget.map(|t| Instant::now() + t),
+            multipv: multipv.max(1),
+            seed: self.rng.gen(),
+        };
+        if self.threads <= 1 {
+            return self.iterative_deepening(board, &main);
+        }
+
+        let seeds = (1..self.threads)
+            .map(|_| self.rng.gen())
+            .collect::<Vec<u64>>();
+        let helpers_stop = AtomicBool::new(false);
+        let this = &*self;
+        thread::scope(|s| {
+            let helpers = (1..this.threads)
+                .map(|id| {
+                    let mut board = board.clone();
+                    let helper = SearchThread {
+                        stop: &helpers_stop,
+                        id,
+                        handle: None,
+                        max_depth,
+                        node_limit: None,
+                        deadline: None,
+                        multipv: 1,
+                        seed: seeds[id - 1],
+                    };
+                    s.spawn(move || this.iterative_deepening(&mut board, &helper)[0].nodes)
+                })
+                .collect::<Vec<_>>();
+
+            let mut results = this.iterative_deepening(board, &main);
+            helpers_stop.store(true, Ordering::Relaxed);
+            for helper in helpers {
+                results[0].nodes += helper.join().expect("search thread panicked");
             }
+            results
+        })
+    }
+
+    /// Iterative deepening up to `max_depth`. Every iteration after the
+    /// first starts with an aspiration window around the previous score
+    /// and widens it whenever the score falls outside.
+    ///
+    /// For more than one line the best move is searched first, then the best
+    /// of the remaining moves and so on, each one is moved to the front.
+    fn iterative_deepening(&self, board: &mut Board, thread: &SearchThread) -> Vec<SearchResult> {
+        let mut root_moves = board.get_pseudo_legal_moves();
+
+        let mut rng = StdRng::seed_from_u64(thread.seed);
+        root_moves.shuffle(&mut rng);
+        order_moves(board, &mut root_moves);
+        root_moves.retain(|m| {
+            let legal = board.make_move(m).is_ok();
+            if legal {
+                board.unmake_last();
+            }
+            legal
         });
 
-        match board.turn {
-            Player::White => {
-                let mut best_move = (None, isize::MIN);
-                for cmove in pseudo_legal_moves {
-                    if board.make_move(&cmove).is_ok() {
-                        let val = eval(
-                            board,
-                            self.search_depth - 1,
-                            &self.evaluator,
-                            best_move.1,
-                            isize::MAX,
-                            &mut self.cache,
-                        );
-                        if val >= best_move.1 {
-                            best_move = (Some(cmove), val);
-                        }
-                        board.unmake_last();
-                    }
-                }
-                return best_move.0;
-            }
-            Player::Black => {
-                let mut best_move = (None, isize::MAX);
-                for cmove in pseudo_legal_moves {
-                    if board.make_move(&cmove).is_ok() {
-                        let val = eval(
-                            board,
-                            self.search_depth - 1,
-                            &self.evaluator,
-                            isize::MIN,
-                            best_move.1,
-                            &mut self.cache,
-                        );
-                        if val <= best_move.1 {
-                            best_move = (Some(cmove), val);
-                        }
-                        board.unmake_last();
+        let mut searcher = Searcher {
+            evaluator: self.evaluator.as_ref(),
+            cache: &self.cache,
+            stop: thread.stop,
+            node_limit: thread.node_limit,
+            deadline: thread.deadline,
+            stopped: false,
+            options: &self.options,
+            max_ply: thread.max_depth as usize * MAX_EXTENSION_FACTOR,
+            after_null_move: false,
+            nodes: 0,
+        };
+
+        let lines = thread.multipv.min(root_moves.len()).max(1);
+        let mut results = (1..=lines)
+            .map(|multipv| SearchResult {
+                best_move: root_moves.get(multipv - 1).copied(),
+                score: 0,
+                depth: 0,
+                pv: Vec::new(),
+                nodes: 0,
+                multipv,
+            })
+            .collect::<Vec<_>>();
+        if root_moves.is_empty() {
+            return results;
+        }
+        let mut pv = Vec::new();
+
+        // Every other helper skips the first depth so the threads
+        // don't all search the same depth at the same time
+        let first_depth = 1 + (thread.id % 2) as u8;
+        'deepening: for depth in first_depth..=thread.max_depth.max(1) {
+            let mut completed = Vec::with_capacity(lines);
+            for line in 0..lines {
+                let previous = results[line].score;
+                let mut window = ASPIRATION_WINDOW;
+                let (mut alpha, mut beta) = if depth > 1 && previous.abs() < MATE_THRESHOLD {
+                    (previous - window, previous + window)
+                } else {
+                    (-INFINITY, INFINITY)
+                };
+
+                let score = loop {
+                    let score = searcher.search_root(
+                        board,
+                        &mut root_moves[line..],
+                        depth,
+                        alpha,
+                        beta,
+                        &mut pv,
+                    );
+                    if searcher.stopped {
+                        break 'deepening;
+                    } else if score <= alpha && alpha > -INFINITY {
+                        window *= 2;
+                        alpha = (score - window).max(-INFINITY);
+                    } else if score >= beta && beta < INFINITY {
+                        window *= 2;
+                        beta = (score + window).min(INFINITY);
+                    } else {
+                        break score;
                     }
+                };
+
+                completed.push(SearchResult {
+                    best_move: pv.first().copied(),
+                    score,
+                    depth,
+                    pv: pv.clone(),
+                    nodes: searcher.nodes,
+                    multipv: line + 1,
+                });
+            }
+
+            // Only whole depths are reported, a partly searched depth
+            // could be missing the best move
+            results = completed;
+            if let Some(callback) = thread.handle.and_then(|h| h.on_depth.as_ref()) {
+                for result in results.iter() {
+                    callback(result);
                 }
-                return best_move.0;
             }
         }
+
+        for result in results.iter_mut() {
+            result.nodes = searcher.nodes;
+        }
+        results
     }
 
-    pub fn modified(&self) -> Bot {
+    pub fn modified(&self, rng: &mut StdRng) -> Bot {
         Bot {
-            evaluator: self.evaluator.modified(),
+            evaluator: self.evaluator.modified(rng),
             search_depth: self.search_depth,
-            cache: FxHashMap::default(),
+            cache: Arc::new(TranspositionTable::default()),
+            options: self.options.clone(),
+            threads: self.threads,
+            rng: StdRng::seed_from_u64(rng.gen()),
+            book: self.book.clone(),
         }
     }
 
+    /// A bot searching like this one with an evaluator combined from both bots
+    pub fn crossover(&self, other: &Bot, rng: &mut StdRng) -> Bot {
+        let mut child = self.bot_clone();
+        child.evaluator = self.evaluator.crossover(other.evaluator.as_ref(), rng);
+        child.rng = StdRng::seed_from_u64(rng.gen());
+        child
+    }
+
+    /// A copy with its own, empty transposition table
     pub fn bot_clone(&self) -> Bot {
         Bot {
             evaluator: self.evaluator.bot_clone(),
             search_depth: self.search_depth,
-            cache: FxHashMap::default(),
+            cache: Arc::new(TranspositionTable::default()),
+            options: self.options.clone(),
+            threads: self.threads,
+            rng: self.rng.clone(),
+            book: self.book.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluators::material_evaluator::MaterialEvaluator, piece::Piece};
+
+    fn bot(depth: u8) -> Bot {
+        Bot::new(Box::new(MaterialEvaluator::default()), depth)
+    }
+
+    /// A board with only the given pieces, like `"Kg1 Ra1 Pf2"`, and no castling
+    fn board(white: &str, black: &str, turn: Player) -> Board {
+        let mut board = Board {
+            turn,
+            can_castle_short: [false; 2],
+            can_castle_long: [false; 2],
+            piece_bitboards: [0; 6],
+            white_piece_bitboard: 0,
+            black_piece_bitboard: 0,
+            ..Board::default()
+        };
+        for (pieces, player) in [(white, Player::White), (black, Player::Black)] {
+            for piece in pieces.split_whitespace() {
+                let mut chars = piece.chars();
+                let kind = match chars.next().unwrap() {
+                    'P' => Piece::Pawn,
+                    'N' => Piece::Knight,
+                    'B' => Piece::Bishop,
+                    'R' => Piece::Rook,
+                    'Q' => Piece::Queen,
+                    _ => Piece::King,
+                };
+                let col = chars.next().unwrap() as u64 - 'a' as u64;
+                let row = chars.next().unwrap() as u64 - '1' as u64;
+                let bit = 1 << (row * 8 + col);
+                board.piece_bitboards[kind.bitboard_index()] |= bit;
+                match player {
+                    Player::White => board.white_piece_bitboard |= bit,
+                    Player::Black => board.black_piece_bitboard |= bit,
+                }
+            }
+        }
+        board
+    }
+
+    fn play(moves: &[&str]) -> Board {
+        let mut board = Board::default();
+        for m in moves {
+            board.make_move(&m.parse().unwrap()).unwrap();
+        }
+        board
+    }
+
+    /// Plain negamax without pruning or a cache
+    fn minimax(evaluator: &dyn Eval, board: &mut Board, depth: u8, ply: usize) -> isize {
+        match board.get_game_state() {
+            GameState::Draw => return 0,
+            GameState::Win(_) => return -(MATE_SCORE - ply as isize),
+            GameState::Playing => (),
+        }
+        if depth == 0 {
+            return evaluator.evaluate_relative(board);
+        }
+        let mut best = -INFINITY;
+        for cmove in board.get_pseudo_legal_moves() {
+            if board.make_move(&cmove).is_ok() {
+                best = best.max(-minimax(evaluator, board, depth - 1, ply + 1));
+                board.unmake_last();
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let mut board = board("Kg1 Ra1", "Kg8 Pf7 Pg7 Ph7", Player::White);
+        let result = bot(2).search(&mut board);
+        assert_eq!(result.best_move, Some("a1a8".parse().unwrap()));
+        assert_eq!(result.mate_in(), Some(1));
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        let mut board = board("Kg1 Ra2 Rb1", "Kh8", Player::White);
+        let result = bot(3).search(&mut board);
+        assert_eq!(result.mate_in(), Some(2));
+        assert_eq!(result.pv.len(), 3);
+        for m in &result.pv {
+            board.make_move(m).unwrap();
+        }
+        assert_eq!(board.get_game_state(), GameState::Win(Player::White));
+    }
+
+    #[test]
+    fn takes_hanging_queen() {
+        let mut board = play(&["e2e4", "d7d5", "d1g4"]);
+        let result = bot(3).search(&mut board);
+        assert_eq!(result.best_move, Some("c8g4".parse().unwrap()));
+    }
+
+    /// Aspiration windows and null window searches may only save work,
+    /// never change the score of a fixed depth search
+    #[test]
+    fn same_score_as_plain_negamax() {
+        for mut board in [
+            board("Kg1 Rd1 Nc3 Pf2 Pg2", "Kg8 Qd5 Bb7 Pf7 Pg7", Player::White),
+            board("Kg1 Qd1 Pf2 Pg2", "Kg8 Rd8 Pf7 Pg7", Player::Black),
+        ] {
+            let evaluator = MaterialEvaluator::default();
+            let expected = minimax(&evaluator, &mut board, 3, 0);
+            assert_eq!(bot(3).search(&mut board).score, expected);
+        }
+    }
+
+    /// The selective search must still see short tactics
+    #[test]
+    fn options_keep_best_move() {
+        for options in ["none", "nmp", "lmr", "ext", "all"] {
+            let mut bot = bot(4);
+            bot.options = options.parse().unwrap();
+
+            let mut mate_in_one = board("Kg1 Ra1", "Kg8 Pf7 Pg7 Ph7", Player::White);
+            let result = bot.search(&mut mate_in_one);
+            assert_eq!(result.best_move, Some("a1a8".parse().unwrap()), "{options}");
+
+            let mut mate_in_two = board("Kg1 Ra2 Rb1", "Kh8", Player::White);
+            assert_eq!(bot.search(&mut mate_in_two).mate_in(), Some(2), "{options}");
+
+            let mut queen = board("Kg1 Rd1 Pf2 Pg2 Ph2", "Kg8 Qd5 Pf7 Pg7 Ph7", Player::White);
+            let result = bot.search(&mut queen);
+            assert_eq!(result.best_move, Some("d1d5".parse().unwrap()), "{options}");
+        }
+    }
+
+    /// Every line has the exact score of its first move, best line first
+    #[test]
+    fn multipv_lines() {
+        let mut board = board("Kg1 Rd1 Pf2 Pg2 Ph2", "Kg8 Qd5 Pf7 Pg7 Ph7", Player::White);
+        let results = bot(3).analyse(&mut board, &SearchLimits::depth(3), 3);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].best_move, Some("d1d5".parse().unwrap()));
+
+        let evaluator = MaterialEvaluator::default();
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.multipv, i + 1);
+            assert_eq!(result.depth, 3);
+            board.make_move(&result.best_move.unwrap()).unwrap();
+            assert_eq!(result.score, -minimax(&evaluator, &mut board, 2, 1));
+            board.unmake_last();
+        }
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_ne!(results[1].best_move, results[2].best_move);
+    }
+
+    #[test]
+    fn multipv_is_capped_by_legal_moves() {
+        let mut board = board("Kh1", "Ka8", Player::White);
+        let results = bot(2).analyse(&mut board, &SearchLimits::depth(2), 10);
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn node_limit_stops_search() {
+        let mut board = Board::default();
+        let limits = SearchLimits {
+            nodes: Some(1000),
+            ..Default::default()
+        };
+        let results = bot(4).analyse(&mut board, &limits, 1);
+        // Moves left on the way back up still count a node each
+        assert!(results[0].nodes < 2000, "{}", results[0].nodes);
+        assert!(results[0].depth < 4);
+        assert!(results[0].best_move.is_some());
+    }
+
+    #[test]
+    fn cache_hits_give_same_result() {
+        let mut board = play(&["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]);
+        let mut bot = bot(4);
+        let first = bot.search(&mut board);
+        let second = bot.search(&mut board);
+        assert_eq!(second.score, first.score);
+        assert!(second.nodes < first.nodes);
+    }
+
+    #[test]
+    fn clones_share_the_cache() {
+        let mut board = play(&["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]);
+        let mut bot = bot(4);
+        bot.search(&mut board);
+        let shared = bot.clone().search(&mut board);
+        let fresh = bot.bot_clone().search(&mut board);
+        assert_eq!(shared.score, fresh.score);
+        assert!(shared.nodes < fresh.nodes);
+    }
+
+    #[test]
+    fn helper_threads_keep_best_move() {
+        let mut bot = bot(4);
+        bot.threads = 4;
+
+        let mut mate_in_two = board("Kg1 Ra2 Rb1", "Kh8", Player::White);
+        assert_eq!(bot.search(&mut mate_in_two).mate_in(), Some(2));
+
+        let mut queen = board("Kg1 Rd1 Pf2 Pg2 Ph2", "Kg8 Qd5 Pf7 Pg7 Ph7", Player::White);
+        let result = bot.search(&mut queen);
+        assert_eq!(result.best_move, Some("d1d5".parse().unwrap()));
+    }
+    #[test]
+    fn stopped_search_still_has_a_move() {
+        let mut board = Board::default();
+        let handle = SearchHandle::new();
+        handle.stop();
+        let result = bot(30).search_with(&mut board, &handle);
+        assert_eq!(result.depth, 0);
+        assert!(board.clone().make_move(&result.best_move.unwrap()).is_ok());
+    }
+
+    /// Stopping in the middle keeps the last completed depth
+    #[test]
+    fn stop_keeps_last_depth() {
+        for threads in [1, 2] {
+            let stop = Arc::new(AtomicBool::new(false));
+            let flag = stop.clone();
+            let handle = SearchHandle {
+                stop,
+                on_depth: Some(Arc::new(move |result: &SearchResult| {
+                    if result.depth >= 2 {
+                        flag.store(true, Ordering::Relaxed);
+                    }
+                })),
+            };
+            let mut bot = bot(30);
+            bot.threads = threads;
+            let mut board = board("Kg1 Rd1 Pf2 Pg2 Ph2", "Kg8 Qd5 Pf7 Pg7 Ph7", Player::White);
+            let result = bot.search_with(&mut board, &handle);
+            assert_eq!(result.depth, 2);
+            assert_eq!(result.best_move, Some("d1d5".parse().unwrap()));
         }
     }
 }
@@ -0,0 +1,213 @@
+use std::fs::{read_to_string, write};
+
+use chlang::board::Board;
+use chlang::cli;
+use chlang::evaluators::evaluator_0::{self, BYTES};
+
+const HELP: &str = "Tune the values of an evaluator_0 bot on positions with known results
+
+Every line of the data file is a position in FEN followed by the result of the
+game it is from, from white's perspective: 1-0, 0-1, 1/2-1/2 or 1.0, 0.5, 0.0.
+The result may be in brackets or quotes. Fields can also be separated by |, then
+the first field is the FEN and the last one the result.
+
+The values are changed one at a time as long as that brings the evaluation,
+turned into an expected score by a sigmoid, closer to the results.
+
+Usage: tune --data <FILE> [OPTIONS]
+
+Options:
+  --data <FILE>          Positions labeled with results
+  --bot <BOT>            evaluator_0 bot to start from [default: DEFAULT]
+  --k <K>                Scale of the sigmoid [default: fitted to the start bot]
+  --iterations <N>       Stop after this many passes over all values [default: until no value changes]
+  --step <N>             Change of a value tried in every pass [default: 1]
+  --out <FILE>           Write the tuned bot string to a file instead of stdout
+  --help                 Print this help";
+
+/// Highest value a byte can have and still be printable in the string representation
+const MAX_VALUE: isize = 126 - 33;
+
+/// Result of a game from white's perspective
+fn parse_result(s: &str) -> Option<f64> {
+    match s.trim_matches(|c: char| "[]\";".contains(c)) {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        r => r.parse::<f64>().ok().filter(|r| (0.0..=1.0).contains(r)),
+    }
+}
+
+fn parse_line(line: &str) -> Result<(Board, f64), String> {
+    let (fen, result) = match line.split_once('|') {
+        Some((fen, rest)) => (fen, rest.rsplit('|').next().unwrap_or(rest)),
+        None => line
+            .trim()
+            .rsplit_once(char::is_whitespace)
+            .ok_or("missing result")?,
+    };
+    let result = parse_result(result.trim()).ok_or(format!("invalid result {result:?}"))?;
+    Ok((Board::from_fen(fen.trim())?, result))
+}
+
+/// Expected score of white for an evaluation
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+fn error(evals: &[f64], results: &[f64], k: f64) -> f64 {
+    evals
+        .iter()
+        .zip(results)
+        .map(|(e, r)| (r - sigmoid(*e, k)).powi(2))
+        .sum::<f64>()
+        / evals.len() as f64
+}
+
+/// The scale with the lowest error, found by golden section search. The error
+/// only has one minimum in the scale.
+fn fit_k(evals: &[f64], results: &[f64]) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (0.0, 100.0);
+    for _ in 0..100 {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if error(evals, results, a) < error(evals, results, b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Positions and results with the features of every position, see
+/// `evaluator_0::features`. Only features that aren't zero are kept, by byte,
+/// so that changing a byte only touches the positions it matters for.
+struct Data {
+    results: Vec<f64>,
+    features: Vec<Vec<(usize, f64)>>,
+}
+
+impl Data {
+    fn read(path: &str) -> Result<Self, String> {
+        let text = read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let mut results = vec![];
+        let mut features = vec![vec![]; BYTES];
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (mut board, result) =
+                parse_line(line).map_err(|e| format!("{path}:{}: {e}", n + 1))?;
+            for (byte, f) in evaluator_0::features(&mut board).iter().enumerate() {
+                if *f != 0 {
+                    features[byte].push((results.len(), *f as f64));
+                }
+            }
+            results.push(result);
+        }
+        if results.is_empty() {
+            return Err(format!("{path} has no positions"));
+        }
+        Ok(Self { results, features })
+    }
+
+    fn evals(&self, values: &[u8]) -> Vec<f64> {
+        let mut evals = vec![0.0; self.results.len()];
+        for (byte, features) in self.features.iter().enumerate() {
+            for (position, f) in features {
+                evals[*position] += f * values[byte] as f64;
+            }
+        }
+        evals
+    }
+}
+
+/// One pass of coordinate descent: every value is moved by `step` up or down
+/// if that lowers the error. Returns whether a value changed.
+fn pass(data: &Data, values: &mut [u8], evals: &mut [f64], k: f64, step: isize) -> bool {
+    let n = evals.len() as f64;
+    let mut changed = false;
+    for (byte, features) in data.features.iter().enumerate() {
+        for delta in [step, -step] {
+            let new = (values[byte] as isize + delta).clamp(0, MAX_VALUE);
+            let delta = (new - values[byte] as isize) as f64;
+            if delta == 0.0 {
+                continue;
+            }
+            // Only the positions with this feature change their error
+            let change = features
+                .iter()
+                .map(|(p, f)| {
+                    let r = data.results[*p];
+                    (r - sigmoid(evals[*p] + f * delta, k)).powi(2)
+                        - (r - sigmoid(evals[*p], k)).powi(2)
+                })
+                .sum::<f64>()
+                / n;
+            if change < 0.0 {
+                values[byte] = new as u8;
+                for (p, f) in features {
+                    evals[*p] += f * delta;
+                }
+                changed = true;
+                break;
+            }
+        }
+    }
+    changed
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // skip name of program
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if cli::take_switch(&mut args, "--help") {
+        println!("{HELP}");
+        return Ok(());
+    }
+
+    let data_path = cli::take_flag(&mut args, "--data")?.ok_or("--data is required")?;
+    let bot = cli::take_flag(&mut args, "--bot")?;
+    let k = cli::take_parsed::<f64>(&mut args, "--k")?;
+    let iterations = cli::take_parsed::<usize>(&mut args, "--iterations")?;
+    let step = cli::take_parsed::<u8>(&mut args, "--step")?.unwrap_or(1);
+    let out = cli::take_flag(&mut args, "--out")?;
+    cli::finish(&args)?;
+
+    let bot = cli::bot(bot.as_deref().unwrap_or("DEFAULT"))?;
+    let rep = bot.evaluator.string_rep();
+    if rep.len() != BYTES {
+        Err(format!(
+            "only evaluator_0 bots can be tuned, {rep:?} is not one"
+        ))?;
+    }
+    let mut values = rep.bytes().map(|b| b - 33).collect::<Vec<_>>();
+
+    let data = Data::read(&data_path)?;
+    println!("positions: {}", data.results.len());
+    let mut evals = data.evals(&values);
+    let k = k.unwrap_or_else(|| fit_k(&evals, &data.results));
+    println!("k: {k:.4}");
+    println!("error: {:.6}", error(&evals, &data.results, k));
+
+    let mut iteration = 0;
+    while iterations.is_none_or(|i| iteration < i) {
+        let changed = pass(&data, &mut values, &mut evals, k, step.max(1) as isize);
+        iteration += 1;
+        println!(
+            "iteration {iteration}: error {:.6}",
+            error(&evals, &data.results, k)
+        );
+        if !changed {
+            break;
+        }
+    }
+
+    let rep = String::from(evaluator_0::Evaluator::from(values.as_slice()));
+    match out {
+        Some(path) => write(path, rep)?,
+        None => println!("{rep}"),
+    }
+    Ok(())
+}
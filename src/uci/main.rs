@@ -0,0 +1,174 @@
+use chlang::board::{Board, Player};
+use chlang::cmove::Move;
+use chlang::parse;
+use chlang::tree_evaluator::{SearchHandle, SearchLimits, SearchResult, MAX_DEPTH};
+use chlang::User;
+use std::io::{BufRead, Write};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Print a line and flush right away, the GUI is waiting on the other end of the pipe
+fn send(line: &str) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{line}");
+    let _ = stdout.flush();
+}
+
+fn info(result: &SearchResult) -> String {
+    let score = match result.mate_in() {
+        Some(moves) => format!("mate {moves}"),
+        None => format!("cp {}", result.score),
+    };
+    let pv = result
+        .pv
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "info depth {} multipv {} score {} nodes {} pv {}",
+        result.depth, result.multipv, score, result.nodes, pv
+    )
+}
+
+/// `position [startpos | fen <fen>] [moves <move>...]`
+fn position<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Board, &'static str> {
+    let mut board = match tokens.next() {
+        Some("startpos") => {
+            tokens.next();
+            Board::default()
+        }
+        Some("fen") => {
+            let fen = tokens
+                .by_ref()
+                .take_while(|t| *t != "moves")
+                .collect::<Vec<_>>()
+                .join(" ");
+            Board::from_fen(&fen)?
+        }
+        _ => return Err("expected startpos or fen"),
+    };
+    for m in tokens {
+        board
+            .make_move(&m.parse::<Move>()?)
+            .map_err(|_| "illegal move")?;
+    }
+    Ok(board)
+}
+
+/// Limits of a `go` command, `infinite` searches until `stop`
+fn limits<'a>(mut tokens: impl Iterator<Item = &'a str>, turn: &Player) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    while let Some(token) = tokens.next() {
+        let mut value = || tokens.next().and_then(|v| v.parse::<u64>().ok());
+        match (token, turn) {
+            ("depth", _) => limits.depth = value().map(|d| d.min(MAX_DEPTH as u64) as u8),
+            ("nodes", _) => limits.nodes = value(),
+            ("movetime", _) => limits.movetime = value().map(Duration::from_millis),
+            ("infinite", _) => limits.depth = Some(MAX_DEPTH),
+            ("wtime", Player::White) | ("btime", Player::Black) => {
+                limits.time = value().map(Duration::from_millis)
+            }
+            ("winc", Player::White) | ("binc", Player::Black) => {
+                limits.increment = Duration::from_millis(value().unwrap_or(0))
+            }
+            ("movestogo", _) => limits.moves_to_go = value().map(|m| m as u32),
+            _ => (),
+        }
+    }
+    limits
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut a = std::env::args();
+
+    // skip name of program
+    a.next();
+
+    // The bot is given like for the other binaries, DEFAULT if none is given
+    let mut bot = match a.next() {
+        Some(s) => match parse::parse(&mut std::iter::once(s).chain(a))? {
+            User::Bot(b) => b,
+            User::Human => Err("uci needs a bot")?,
+        },
+        None => match parse::parse(&mut std::iter::once("DEFAULT".to_string()))? {
+            User::Bot(b) => b,
+            User::Human => unreachable!(),
+        },
+    };
+
+    let mut board = Board::default();
+    let mut multipv = 1;
+    // The running search and whether it is infinite
+    let mut search: Option<(SearchHandle, JoinHandle<()>, bool)> = None;
+
+    let stdin = std::io::BufReader::new(std::io::stdin());
+    for line in stdin.lines() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let command = tokens.next().unwrap_or("");
+
+        // Everything except isready waits for a running search, stop and
+        // quit make it return first. An infinite search would never return
+        // on its own, so any other command stops it as well.
+        if !matches!(command, "isready" | "") {
+            if let Some((handle, thread, infinite)) = search.take() {
+                if infinite || matches!(command, "stop" | "quit") {
+                    handle.stop();
+                }
+                thread.join().expect("search thread panicked");
+            }
+        }
+
+        match command {
+            "uci" => {
+                send("id name chlang");
+                send("id author chlang");
+                send("option name MultiPV type spin default 1 min 1 max 256");
+                send("option name Threads type spin default 1 min 1 max 256");
+                send("uciok");
+            }
+            "isready" => send("readyok"),
+            "ucinewgame" => bot.cache.clear(),
+            "setoption" => {
+                // setoption name <name> value <value>
+                let rest = tokens.collect::<Vec<_>>();
+                let value = rest.iter().skip_while(|t| **t != "value").nth(1);
+                let value = value.and_then(|v| v.parse::<usize>().ok()).unwrap_or(1);
+                match rest.get(1).map(|n| n.to_ascii_lowercase()).as_deref() {
+                    Some("multipv") => multipv = value.max(1),
+                    Some("threads") => bot.threads = value.max(1),
+                    _ => send("info string unknown option"),
+                }
+            }
+            "position" => match position(tokens) {
+                Ok(b) => board = b,
+                Err(e) => send(&format!("info string {e}")),
+            },
+            "go" => {
+                // bestmove may only be sent after stop in an infinite search,
+                // even when the search is done before that
+                let infinite = tokens.clone().any(|t| t == "infinite");
+                let limits = limits(tokens, &board.turn);
+                let handle = SearchHandle::with_callback(|result| send(&info(result)));
+                let mut bot = bot.clone();
+                let mut board = board.clone();
+                let thread_handle = handle.clone();
+                let thread = thread::spawn(move || {
+                    let results = bot.analyse_with(&mut board, &limits, multipv, &thread_handle);
+                    while infinite && !thread_handle.is_stopped() {
+                        thread::sleep(Duration::from_millis(5));
+                    }
+                    match results.first().and_then(|r| r.best_move) {
+                        Some(m) => send(&format!("bestmove {m}")),
+                        None => send("bestmove 0000"),
+                    }
+                });
+                search = Some((handle, thread, infinite));
+            }
+            "quit" => break,
+            _ => (),
+        }
+    }
+    Ok(())
+}
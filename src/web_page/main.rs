@@ -5,11 +5,10 @@ use chlang::{
     cmove::Move,
     evaluators::evaluator_0,
     piece::Piece,
-    tree_evaluator::Bot,
+    tree_evaluator::{Bot, SearchHandle, SearchResult},
     User,
 };
-use leptos::{html::Time, logging, prelude::*, task::spawn_local};
-use rustc_hash::FxHashMap;
+use leptos::{logging, prelude::*, task::spawn_local};
 
 const BOT_STRING_LENGTH: usize = 410;
 
@@ -17,39 +16,42 @@ fn main() {
     leptos::mount::mount_to_body(App)
 }
 
-fn check_and_update(
-    string: String,
-    set_board: WriteSignal<board::Board>,
+/// The signals of the page, handed to the event handlers together
+#[derive(Clone, Copy)]
+struct Signals {
     board: ReadSignal<Board>,
-    write_white_player: WriteSignal<User>,
+    set_board: WriteSignal<Board>,
     white_player: ReadSignal<User>,
+    set_white_player: WriteSignal<User>,
     black_player: ReadSignal<User>,
-) {
+    selected_square: ReadSignal<Option<Position>>,
+    set_selected_square: WriteSignal<Option<Position>>,
+    possible_promotion: ReadSignal<Option<Position>>,
+    set_possible_promotion: WriteSignal<Option<Position>>,
+    set_progress: WriteSignal<Option<SearchResult>>,
+}
+
+fn check_and_update(string: String, signals: Signals) {
     let bot = evaluator_0::Evaluator::from_str(&string).unwrap();
     logging::log!("{:?}", bot);
-    let bot = Bot {
-        evaluator: Box::new(bot),
-        search_depth: 3,
-        cache: FxHashMap::default(),
-    };
+    let bot = Bot::new(Box::new(bot), 3);
     let b = Board::default();
-    let u = User::Bot(bot);
-    set_board.set(b);
-    write_white_player.set(u);
-    play(white_player, black_player, board, set_board);
+    let u = User::Bot(Box::new(bot));
+    signals.set_board.set(b);
+    signals.set_white_player.set(u);
+    play(signals);
 }
 
-fn handle_click_on_board(
-    selected_square: ReadSignal<Option<Position>>,
-    set_selected_square: WriteSignal<Option<Position>>,
-    board: ReadSignal<Board>,
-    set_board: WriteSignal<Board>,
-    square_idx: i64,
-    white_player: ReadSignal<User>,
-    black_player: ReadSignal<User>,
-    possible_promotion: ReadSignal<Option<Position>>,
-    set_possible_promotion: WriteSignal<Option<Position>>,
-) {
+fn handle_click_on_board(square_idx: i64, signals: Signals) {
+    let Signals {
+        board,
+        set_board,
+        selected_square,
+        set_selected_square,
+        possible_promotion,
+        set_possible_promotion,
+        ..
+    } = signals;
     if possible_promotion.get().is_some() {
         *set_possible_promotion.write() = None;
     }
@@ -75,7 +77,7 @@ fn handle_click_on_board(
         } else {
             *set_board.write() = b;
             *set_selected_square.write() = None;
-            play(white_player, black_player, board, set_board);
+            play(signals);
         }
     } else {
         let pos = Position::new(square_idx / 8, square_idx % 8);
@@ -83,17 +85,16 @@ fn handle_click_on_board(
     }
 }
 
-fn promote(
-    selected_square: ReadSignal<Option<Position>>,
-    set_selected_square: WriteSignal<Option<Position>>,
-    board: ReadSignal<Board>,
-    set_board: WriteSignal<Board>,
-    white_player: ReadSignal<User>,
-    black_player: ReadSignal<User>,
-    possible_promotion: ReadSignal<Option<Position>>,
-    set_possible_promotion: WriteSignal<Option<Position>>,
-    piece_type: Piece,
-) {
+fn promote(piece_type: Piece, signals: Signals) {
+    let Signals {
+        board,
+        set_board,
+        selected_square,
+        set_selected_square,
+        possible_promotion,
+        set_possible_promotion,
+        ..
+    } = signals;
     let cmove = Move::promotion(
         &selected_square.get().unwrap(),
         &possible_promotion.get().unwrap(),
@@ -110,38 +111,80 @@ fn promote(
     set_board.set(b);
     set_selected_square.set(None);
     set_possible_promotion.set(None);
-    play(white_player, black_player, board, set_board);
+    play(signals);
 }
 
-fn play(
-    white_player: ReadSignal<User>,
-    black_player: ReadSignal<User>,
-    board: ReadSignal<Board>,
-    set_board: WriteSignal<board::Board>,
-) {
+thread_local! {
+    /// Handle of the running bot search, the stop button stops it after the
+    /// current depth
+    static SEARCH: SearchHandle = SearchHandle::new();
+}
+
+/// Search one depth at a time and give the browser a chance to handle
+/// events in between, so the page stays responsive between depths. A search
+/// runs to the end once started, so the stop button only takes effect when
+/// the current depth is done. Lower depths are cheap to redo thanks to the
+/// bot's cache.
+async fn think(
+    bot: &mut Bot,
+    mut board: Board,
+    set_progress: WriteSignal<Option<SearchResult>>,
+) -> Option<Move> {
+    let handle = SEARCH.with(|h| h.clone());
+    handle.reset();
+    let max_depth = bot.search_depth;
+    let mut best_move = None;
+    for depth in 1..=max_depth {
+        bot.search_depth = depth;
+        let result = bot.search_with(&mut board, &handle);
+        best_move = result.best_move;
+        set_progress.set(Some(result));
+        gloo_timers::future::TimeoutFuture::new(0).await;
+        if handle.is_stopped() {
+            break;
+        }
+    }
+    bot.search_depth = max_depth;
+    set_progress.set(None);
+    best_move
+}
+
+fn play(signals: Signals) {
+    let Signals {
+        board,
+        set_board,
+        white_player,
+        black_player,
+        set_progress,
+        ..
+    } = signals;
     match board.get().turn {
         Player::White => match white_player.get() {
-            User::Human => return,
+            User::Human => (),
             User::Bot(mut b) => {
                 spawn_local(async move {
                     gloo_timers::future::TimeoutFuture::new(50).await;
-                    let cmove = b.find_best_move(&mut board.get()).unwrap();
+                    let cmove = think(&mut b, board.get(), set_progress).await.unwrap();
                     let mut b = board.get();
-                    b.make_move(&cmove);
-                    set_board.set(b);
+                    match b.make_move(&cmove) {
+                        Ok(()) => set_board.set(b),
+                        Err(e) => logging::error!("bot played {:?}: {:?}", cmove, e),
+                    }
                     // play(white_player, black_player, board, set_board);
                 });
             }
         },
         Player::Black => match black_player.get() {
-            User::Human => return,
+            User::Human => (),
             User::Bot(mut b) => {
                 spawn_local(async move {
                     gloo_timers::future::TimeoutFuture::new(50).await;
-                    let cmove = b.find_best_move(&mut board.get()).unwrap();
+                    let cmove = think(&mut b, board.get(), set_progress).await.unwrap();
                     let mut b = board.get();
-                    b.make_move(&cmove);
-                    set_board.set(b);
+                    match b.make_move(&cmove) {
+                        Ok(()) => set_board.set(b),
+                        Err(e) => logging::error!("bot played {:?}: {:?}", cmove, e),
+                    }
                     // play(white_player, black_player, board, set_board);
                 });
             }
@@ -167,10 +210,23 @@ fn App() -> impl IntoView {
     let (string, set_string) = signal(String::new());
     let (selected_square, set_selected_square) = signal::<Option<Position>>(None);
 
-    let (black_player, set_black_player) = signal(User::Human);
+    let (black_player, _) = signal(User::Human);
     let (white_player, set_white_player) = signal(User::Human);
     let (possible_promotion, set_possible_promotion) = signal(None);
+    let (progress, set_progress) = signal::<Option<SearchResult>>(None);
     let game_state = move || board.get().get_game_state();
+    let signals = Signals {
+        board,
+        set_board,
+        white_player,
+        set_white_player,
+        black_player,
+        selected_square,
+        set_selected_square,
+        possible_promotion,
+        set_possible_promotion,
+        set_progress,
+    };
 
     let form_or_game = move || {
         view! {
@@ -178,19 +234,21 @@ fn App() -> impl IntoView {
         <div class="top-bar">
             <label for="stringrep">String representation / id</label>
             <input id="string_rep" maxlength=move||BOT_STRING_LENGTH  bind:value=(string,set_string) class:good=move||string.read().len() == BOT_STRING_LENGTH/>
-            <button on:click= move|_| check_and_update(string.get(), set_board, board, set_white_player, white_player, black_player) >play</button>
+            <button on:click= move|_| check_and_update(string.get(), signals) >play</button>
             <button on:click= move|_| random(set_string)>randomize</button>
+            <button on:click= move|_| SEARCH.with(|h| h.stop())>stop after this depth</button>
+            <span>{move || progress.get().map(|r| format!("depth {} score {}", r.depth, r.score))}</span>
         </div>
 
         <div style="display: flex; flex-direction: row; gap: 10px;">
         <div class="board" >
-            {(0..64).into_iter().map(|n| view! {
+            {(0..64).map(|n| view! {
                 <div
                 class:gridodd=move || (n + n/8) %2 == 1
                 class:grideven=move || (n+n/8) %2 == 0
                 class:gridselected=move || selected_square.get().is_some_and(|x| x.row*8 + x.col == n)
 
-                on:click=move|_| handle_click_on_board(selected_square, set_selected_square, board,set_board, n, white_player, black_player, possible_promotion, set_possible_promotion)
+                on:click=move|_| handle_click_on_board(n, signals)
 
                 inner_html={
 
@@ -249,10 +307,10 @@ fn App() -> impl IntoView {
             }).collect::<Vec<_>>()}
         </div>
         <p style:display=move|| if possible_promotion.get().is_some() {"block"} else {"none"} >
-        <img class = "grideven" src="images/white-queen.png" on:click= move|_| promote(selected_square, set_selected_square, board, set_board, white_player, black_player, possible_promotion, set_possible_promotion, Piece::Queen) />
-        <img class = "grideven" src="images/white-rook.png" on:click= move|_| promote(selected_square, set_selected_square, board, set_board, white_player, black_player, possible_promotion, set_possible_promotion, Piece::Rook) />
-        <img class = "grideven" src="images/white-bishop.png" on:click= move|_| promote(selected_square, set_selected_square, board, set_board, white_player, black_player, possible_promotion, set_possible_promotion, Piece::Bishop) />
-        <img class = "grideven" src="images/white-knight.png" on:click= move|_| promote(selected_square, set_selected_square, board, set_board, white_player, black_player, possible_promotion, set_possible_promotion, Piece::Knight) />
+        <img class = "grideven" src="images/white-queen.png" on:click= move|_| promote(Piece::Queen, signals) />
+        <img class = "grideven" src="images/white-rook.png" on:click= move|_| promote(Piece::Rook, signals) />
+        <img class = "grideven" src="images/white-bishop.png" on:click= move|_| promote(Piece::Bishop, signals) />
+        <img class = "grideven" src="images/white-knight.png" on:click= move|_| promote(Piece::Knight, signals) />
 
         </p>
         </div>